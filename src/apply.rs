@@ -0,0 +1,88 @@
+//! Reconciling the servers of a host with a manifest
+use std::fs::read_to_string;
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Clap;
+use docker_game_servers::manifest::{Change, Manifest};
+use futures_util::TryStreamExt;
+
+use crate::host::Host;
+use crate::license::ensure_accepted;
+
+#[derive(Clap)]
+pub struct Apply {
+    /// The TOML manifest listing the servers e.g. `servers.toml`
+    ///
+    /// Compose files, including those of `dgs server export --format compose`,
+    /// cannot be applied.
+    #[clap(short, long)]
+    file: PathBuf,
+    /// Remove servers created from a manifest that are no longer listed
+    #[clap(long)]
+    prune: bool,
+    /// Apply the changes without asking
+    #[clap(short, long)]
+    yes: bool,
+}
+
+/// Shows the changes needed to match the manifest and applies them once confirmed
+pub async fn apply(Apply { file, prune, yes }: Apply, host: &Host) -> Result<()> {
+    let mut manifest = Manifest::parse(
+        &read_to_string(&file)
+            .with_context(|| format!("Unable to read manifest `{}`", file.display()))?,
+    )
+    .with_context(|| format!("Invalid manifest `{}`", file.display()))?;
+    for server in &mut manifest.servers {
+        let (game, ..) = server.setup()?;
+        ensure_accepted(game, server.accept_eula)?;
+        server.accept_eula = true;
+    }
+
+    let plan = host.manager.plan(&manifest, prune).await?;
+    for change in &plan.changes {
+        println!("{}", change);
+    }
+    if plan.is_empty() {
+        println!("Nothing to do");
+        return Ok(());
+    }
+    if !yes {
+        if !termion::is_tty(&stdin()) {
+            bail!("Confirm the changes with `--yes`");
+        }
+        print!("Apply these changes? [y/N] ");
+        stdout().flush()?;
+        let mut answer = String::new();
+        stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            bail!("The changes were not applied");
+        }
+    }
+
+    for change in &plan.changes {
+        if let Change::Create(server)
+        | Change::Recreate {
+            manifest: server, ..
+        } = change
+        {
+            let (game, options, _) = server.setup()?;
+            println!("Pulling {}", game.image);
+            host.manager
+                .pull(game, options.version.as_deref())
+                .try_for_each(|progress| async move {
+                    println!("{}", progress);
+                    Ok(())
+                })
+                .await?;
+        }
+    }
+    for change in &plan.changes {
+        if !matches!(change, Change::Unchanged(_)) {
+            println!("{}", change);
+            host.manager.apply(change).await?;
+        }
+    }
+    Ok(())
+}
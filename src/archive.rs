@@ -0,0 +1,52 @@
+//! Just enough of the tar format to upload files into containers
+use anyhow::{bail, Result};
+
+const BLOCK: usize = 512;
+
+/// An uncompressed tar archive of regular files
+#[derive(Default)]
+pub struct Archive(Vec<u8>);
+
+impl Archive {
+    /// Adds a file readable by everyone, `path` is relative to the upload path
+    pub fn file(mut self, path: &str, content: &[u8]) -> Result<Self> {
+        let path = path.trim_start_matches('/');
+        if path.len() > 100 {
+            bail!("Path is too long to be archived: `{}`", path);
+        }
+        let mut header = [0; BLOCK];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        write_octal(&mut header[100..108], 0o644);
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        write_octal(&mut header[124..136], content.len() as u64);
+        write_octal(&mut header[136..148], 0);
+        // Regular file
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        // The checksum is calculated with the checksum field set to spaces
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u64 = header.iter().map(|b| u64::from(*b)).sum();
+        write_octal(&mut header[148..155], checksum);
+
+        self.0.extend_from_slice(&header);
+        self.0.extend_from_slice(content);
+        let padding = (BLOCK - content.len() % BLOCK) % BLOCK;
+        self.0.resize(self.0.len() + padding, 0);
+        Ok(self)
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        // The end of an archive is marked by two empty blocks
+        self.0.resize(self.0.len() + 2 * BLOCK, 0);
+        self.0
+    }
+}
+
+/// Writes `value` as zero padded octal number followed by a null byte
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
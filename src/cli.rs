@@ -1,19 +1,31 @@
 use std::io;
-use std::ops::Deref;
 use std::path::PathBuf;
-use std::str::FromStr;
 
 use clap::{App, ArgEnum, Clap};
 
-use crate::server::{ServerCmd, ServerFilter};
+use crate::apply::Apply;
+use crate::server::{ListOptions, ServerCmd};
 
 #[derive(Clap)]
-#[clap(version = "0.1", author = "ModProg <dev@modprog.de>", bin_name = "dgs")]
+#[clap(
+    version = "0.1",
+    author = "ModProg <dev@modprog.de>",
+    bin_name = "dgs",
+    after_help = "EXIT CODES:\n    1  Any other error\n    2  Invalid arguments\n    3  Container engine unreachable\n    4  Pulling the image failed\n    5  Port unavailable\n    6  Server not found\n    7  Ambiguous server name\n    8  Not supported by the game"
+)]
 pub struct Opt {
     #[clap(short, long)]
     pub podman_user: bool,
     #[clap(short = 'P', long, conflicts_with = "podman-user")]
     pub podman_system: bool,
+    /// Use a host from the config instead of the local engine
+    #[clap(
+        short = 'H',
+        long,
+        conflicts_with = "podman-user",
+        conflicts_with = "podman-system"
+    )]
+    pub host: Option<String>,
     #[clap(subcommand)]
     pub cmd: Command,
 }
@@ -38,8 +50,17 @@ pub enum Command {
         #[clap(short, long, conflicts_with = "filename", conflicts_with = "print")]
         system: bool,
     },
+    /// Check for problems with the setup of dgs
+    ///
+    /// Checks the engine, sockets, images, ports, leftover containers and
+    /// volumes as well as the shell completions.
+    Doctor,
+    /// Create, recreate and remove servers to match a manifest
+    ///
+    /// Shows the planned changes and asks before applying them.
+    Apply(Apply),
     /// List servers
-    Servers(ServerFilter),
+    Servers(ListOptions),
     /// Manage servers
     Server {
         #[clap(subcommand)]
@@ -111,7 +132,7 @@ impl ShellType {
                 generate::<generators::Fish, _>(app, name, buffer);
                 // Sub completions for the `help` command
                 // because clap cannot do this currently
-                let commands = "completions games server servers";
+                let commands = "apply completions doctor games server servers";
                 writeln!(buffer,
                          r#"complete -c dgs -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from {}" -f -a "{}" -r"#,
                          commands, commands)
@@ -122,51 +143,3 @@ impl ShellType {
         }
     }
 }
-
-#[derive(Clone)]
-pub struct LowerCaseString(String);
-
-impl FromStr for LowerCaseString {
-    type Err = !;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(s.to_lowercase()))
-    }
-}
-
-impl From<&str> for LowerCaseString {
-    fn from(s: &str) -> Self {
-        Self(s.into())
-    }
-}
-
-impl From<String> for LowerCaseString {
-    fn from(s: String) -> Self {
-        Self(s)
-    }
-}
-
-impl From<LowerCaseString> for String {
-    fn from(lcs: LowerCaseString) -> Self {
-        lcs.0
-    }
-}
-
-impl Deref for LowerCaseString {
-    type Target = str;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl PartialEq<String> for LowerCaseString {
-    fn eq(&self, other: &String) -> bool {
-        *other == self.0
-    }
-}
-impl PartialEq<String> for &LowerCaseString {
-    fn eq(&self, other: &String) -> bool {
-        *other == self.0
-    }
-}
@@ -0,0 +1,57 @@
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::TIME_OUT;
+
+/// The contents of `dgs/config.toml` in the users config directory
+#[derive(Deserialize, Default)]
+pub struct Config {
+    /// Additional container engines dgs can manage
+    #[serde(default)]
+    pub hosts: Vec<HostConfig>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct HostConfig {
+    pub name: String,
+    /// e.g. `unix:///var/run/docker.sock` or `tcp://192.168.0.42:2375`
+    pub uri: String,
+    /// Seconds to wait for this host before giving up
+    #[serde(default = "default_time_out")]
+    pub time_out: u64,
+}
+
+fn default_time_out() -> u64 {
+    TIME_OUT
+}
+
+impl Config {
+    pub fn dir() -> PathBuf {
+        dirs::config_dir()
+            .expect("There should be a config dir ($XDG_CONFIG_HOME)")
+            .join("dgs")
+    }
+
+    /// Loads the config file, a missing file is treated as an empty config
+    pub fn load() -> Result<Self> {
+        let path = Self::dir().join("config.toml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        toml::from_str(
+            &read_to_string(&path)
+                .with_context(|| format!("Unable to read config `{}`", path.display()))?,
+        )
+        .map_err(|e| anyhow!("Invalid config `{}`: {}", path.display(), e))
+    }
+
+    pub fn host(&self, name: &str) -> Result<&HostConfig> {
+        self.hosts
+            .iter()
+            .find(|host| host.name == name)
+            .ok_or_else(|| anyhow!("There is no host named `{}` in the config", name))
+    }
+}
@@ -0,0 +1,498 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use bollard::models::{ContainerStateStatusEnum, PortTypeEnum};
+use docker_game_servers::{
+    image_repository, BasicServerInfo, Error, InvalidServer, ServerFilter, GAMES,
+};
+use portpicker::{is_free_tcp, is_free_udp};
+
+use crate::cli::{Opt, ShellType};
+use crate::host::{Host, CLIENT_VERSION};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Pass => write!(f, "pass"),
+            Outcome::Warn => write!(f, "warn"),
+            Outcome::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// The result of a single check, `details` are printed indented below it
+struct Check {
+    name: &'static str,
+    outcome: Outcome,
+    summary: String,
+    details: Vec<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, outcome: Outcome, summary: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome,
+            summary: summary.into(),
+            details: Vec::new(),
+        }
+    }
+
+    fn pass(name: &'static str, summary: impl Into<String>) -> Self {
+        Self::new(name, Outcome::Pass, summary)
+    }
+
+    fn warn(name: &'static str, summary: impl Into<String>) -> Self {
+        Self::new(name, Outcome::Warn, summary)
+    }
+
+    fn fail(name: &'static str, summary: impl Into<String>) -> Self {
+        Self::new(name, Outcome::Fail, summary)
+    }
+
+    fn details(mut self, details: impl IntoIterator<Item = String>) -> Self {
+        self.details.extend(details);
+        self
+    }
+
+    /// Turns a failed request to the engine into a failed check
+    fn from_result(name: &'static str, result: Result<Self>) -> Self {
+        result.unwrap_or_else(|error| Self::fail(name, format!("Request failed: {:#}", error)))
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.outcome, self.name, self.summary)?;
+        for detail in &self.details {
+            write!(f, "\n       {}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the environment dgs runs in and prints a report
+///
+/// Fails if any check failed.
+pub async fn doctor(opt: &Opt, host: &Host) -> Result<()> {
+    let mut checks = vec![sockets(opt)];
+    let engine = engine(host).await;
+    let reachable = engine.outcome != Outcome::Fail;
+    checks.push(engine);
+    if reachable {
+        checks.push(Check::from_result("Images", images(host).await));
+        match host.manager.list_checked(&ServerFilter::default()).await {
+            Ok((servers, invalid)) => {
+                checks.push(containers(&servers, &invalid));
+                checks.push(leftovers(&servers));
+                checks.push(Check::from_result("Ports", ports(host, &servers).await));
+            }
+            Err(error) => checks.push(Check::fail(
+                "Containers",
+                format!("Unable to list containers: {:#}", error),
+            )),
+        }
+        checks.push(Check::from_result("Volumes", volumes(host).await));
+    }
+    checks.push(completions());
+
+    for check in &checks {
+        println!("{}", check);
+    }
+    if !reachable {
+        println!("Checks that need the engine were skipped");
+    }
+    let count = |outcome| {
+        checks
+            .iter()
+            .filter(|check| check.outcome == outcome)
+            .count()
+    };
+    println!(
+        "\n{} passed, {} warnings, {} failed",
+        count(Outcome::Pass),
+        count(Outcome::Warn),
+        count(Outcome::Fail)
+    );
+    if count(Outcome::Fail) > 0 {
+        bail!("{} checks failed", count(Outcome::Fail));
+    }
+    Ok(())
+}
+
+/// Whether the socket of the selected engine exists
+fn sockets(opt: &Opt) -> Check {
+    const NAME: &str = "Socket";
+    if let Some(host) = &opt.host {
+        return Check::pass(NAME, format!("Using the host `{}` from the config", host));
+    }
+    if let (false, false, Ok(docker_host)) =
+        (opt.podman_user, opt.podman_system, env::var("DOCKER_HOST"))
+    {
+        return Check::pass(NAME, format!("Using `DOCKER_HOST={}`", docker_host));
+    }
+    let sockets = [
+        (
+            "rootless Podman",
+            "--podman-user",
+            dirs::runtime_dir().map(|dir| dir.join("podman/podman.sock")),
+        ),
+        (
+            "Podman",
+            "--podman-system",
+            Some(PathBuf::from("/var/run/podman/podman.sock")),
+        ),
+        ("Docker", "", Some(PathBuf::from("/var/run/docker.sock"))),
+    ];
+    let selected = match (opt.podman_user, opt.podman_system) {
+        (true, _) => 0,
+        (_, true) => 1,
+        _ => 2,
+    };
+    let (name, _, path) = &sockets[selected];
+    let others = sockets
+        .iter()
+        .enumerate()
+        .filter(|(index, (_, _, path))| {
+            *index != selected && path.as_ref().map(|path| path.exists()).unwrap_or(false)
+        })
+        .map(|(_, (name, flag, path))| {
+            let path = path.as_ref().expect("Only existing paths are kept");
+            if flag.is_empty() {
+                format!("Found the {} socket `{}`", name, path.display())
+            } else {
+                format!(
+                    "Found the {} socket `{}`, use it with `{}`",
+                    name,
+                    path.display(),
+                    flag
+                )
+            }
+        });
+    match path {
+        Some(path) if path.exists() => Check::pass(
+            NAME,
+            format!("Using the {} socket `{}`", name, path.display()),
+        )
+        .details(others),
+        Some(path) => {
+            let check = Check::fail(
+                NAME,
+                format!("The {} socket `{}` does not exist", name, path.display()),
+            );
+            match selected {
+                0 => check.details(Some(
+                    "Start it with `systemctl --user enable --now podman.socket`".to_string(),
+                )),
+                1 => check.details(Some(
+                    "Start it with `systemctl enable --now podman.socket`".to_string(),
+                )),
+                _ => check,
+            }
+            .details(others)
+        }
+        None => Check::fail(
+            NAME,
+            "There is no runtime dir ($XDG_RUNTIME_DIR) for the rootless Podman socket",
+        ),
+    }
+}
+
+/// Whether the engine answers and supports the API version dgs uses
+async fn engine(host: &Host) -> Check {
+    const NAME: &str = "Engine";
+    if let Err(error) = host.manager.ping().await {
+        let hint = error.downcast_ref::<Error>().and_then(Error::hint);
+        return Check::fail(NAME, error.to_string()).details(hint.map(String::from));
+    }
+    let version = match host.manager.engine_version().await {
+        Ok(version) => version,
+        Err(error) => {
+            return Check::warn(
+                NAME,
+                format!(
+                    "`{}` is reachable but did not report a version: {}",
+                    host.name, error
+                ),
+            )
+        }
+    };
+    let api_version = version.api_version.unwrap_or_default();
+    let engine = format!(
+        "`{}` runs version {} with API {}",
+        host.name,
+        version.version.as_deref().unwrap_or("?"),
+        api_version
+    );
+    let required = (CLIENT_VERSION.major_version, CLIENT_VERSION.minor_version);
+    let parsed = api_version
+        .split_once('.')
+        .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)));
+    match parsed {
+        Some(api) if api >= required => Check::pass(NAME, engine),
+        Some(_) => Check::fail(NAME, engine).details(Some(format!(
+            "dgs needs at least API {}.{}, update the engine",
+            required.0, required.1
+        ))),
+        None => {
+            Check::warn(NAME, engine).details(Some("Unable to compare the API version".to_string()))
+        }
+    }
+}
+
+/// Which game images are pulled already
+async fn images(host: &Host) -> Result<Check> {
+    let tags = host.manager.pulled_images().await?;
+    let (pulled, missing): (Vec<_>, Vec<_>) = GAMES.iter().partition(|game| {
+        tags.iter()
+            .any(|tag| image_repository(tag) == image_repository(game.image))
+    });
+    let check = Check::pass(
+        "Images",
+        format!("{} of {} game images are pulled", pulled.len(), GAMES.len()),
+    );
+    Ok(if missing.is_empty() {
+        check
+    } else {
+        check.details(Some(format!(
+            "Pulled on first use: {}",
+            missing
+                .iter()
+                .map(|game| game.image)
+                .intersperse(", ")
+                .collect::<String>()
+        )))
+    })
+}
+
+/// Containers labelled as dgs servers that dgs cannot manage
+fn containers(servers: &[BasicServerInfo], invalid: &[InvalidServer]) -> Check {
+    const NAME: &str = "Containers";
+    if invalid.is_empty() {
+        return Check::pass(
+            NAME,
+            format!(
+                "All {} containers labelled as dgs servers are compatible",
+                servers.len()
+            ),
+        );
+    }
+    Check::warn(
+        NAME,
+        format!(
+            "{} of {} containers labelled as dgs servers are not compatible",
+            invalid.len(),
+            servers.len() + invalid.len()
+        ),
+    )
+    .details(invalid.iter().map(|server| {
+        format!(
+            "`{}` ({}, image {}, {}): {}",
+            server.name(),
+            server.id.chars().take(12).collect::<String>(),
+            server.image.as_deref().unwrap_or("-"),
+            server.state.as_deref().unwrap_or("-"),
+            server.reason
+        )
+    }))
+    .details(Some(
+        "Remove these containers or recreate them with `dgs` to manage them".to_string(),
+    ))
+}
+
+/// Temporary servers that were not cleaned up, e.g. because dgs was killed
+///
+/// Running ones might still be in use by a `dgs server tmp` waiting for input.
+fn leftovers(servers: &[BasicServerInfo]) -> Check {
+    const NAME: &str = "Temporary servers";
+    let (running, stopped): (Vec<_>, Vec<_>) = servers
+        .iter()
+        .filter(|server| server.name.starts_with("/dgs-tmp_"))
+        .partition(|server| server.status == ContainerStateStatusEnum::RUNNING);
+    let names = |servers: Vec<&BasicServerInfo>| {
+        servers
+            .iter()
+            .map(|server| server.name.trim_start_matches('/'))
+            .intersperse(" ")
+            .collect::<String>()
+    };
+    match (running.len(), stopped.len()) {
+        (0, 0) => Check::pass(NAME, "No temporary servers are left over"),
+        (running_count, stopped_count) => {
+            let mut check = Check::warn(
+                NAME,
+                format!(
+                    "{} running and {} stopped temporary servers are left over",
+                    running_count, stopped_count
+                ),
+            );
+            if running_count > 0 {
+                check = check.details(Some(format!(
+                    "Stop and remove the running ones with `docker rm -f {}` unless a `dgs server tmp` still uses them",
+                    names(running)
+                )));
+            }
+            if stopped_count > 0 {
+                check = check.details(Some(format!(
+                    "Remove the stopped ones with `docker rm {}`",
+                    names(stopped)
+                )));
+            }
+            check
+        }
+    }
+}
+
+/// Host ports used by multiple servers or taken by something else
+async fn ports(host: &Host, servers: &[BasicServerInfo]) -> Result<Check> {
+    let mut bindings: BTreeMap<(u16, PortTypeEnum), Vec<&BasicServerInfo>> = BTreeMap::new();
+    for server in servers {
+        let port_bindings = host
+            .manager
+            .inspect(&server.id)
+            .await?
+            .host_config
+            .and_then(|host_config| host_config.port_bindings)
+            .unwrap_or_default();
+        for (port, port_bindings) in port_bindings {
+            let typ = match port.split_once('/').map(|(_, typ)| typ.parse()) {
+                Some(Ok(typ)) => typ,
+                _ => continue,
+            };
+            for host_port in port_bindings
+                .into_iter()
+                .flatten()
+                .filter_map(|binding| binding.host_port?.parse().ok())
+            {
+                bindings.entry((host_port, typ)).or_default().push(server);
+            }
+        }
+    }
+
+    let mut warnings = Vec::new();
+    let mut failures = Vec::new();
+    for ((port, typ), servers) in bindings {
+        if servers.len() > 1 {
+            warnings.push(format!(
+                "{}/{} is used by {}, only one of them can run at a time",
+                port,
+                typ,
+                servers
+                    .iter()
+                    .map(|server| format!("`{}`", server.name))
+                    .intersperse(", ".to_owned())
+                    .collect::<String>()
+            ));
+        }
+        // Running servers hold their ports, only the local host can be checked
+        if host.address != "localhost"
+            || servers
+                .iter()
+                .any(|server| server.status == ContainerStateStatusEnum::RUNNING)
+        {
+            continue;
+        }
+        let free = match typ {
+            PortTypeEnum::UDP => is_free_udp(port),
+            _ => is_free_tcp(port),
+        };
+        if !free {
+            failures.push(format!(
+                "{}/{} is taken by another program, `{}` cannot start",
+                port, typ, servers[0].name
+            ));
+        }
+    }
+    Ok(if !failures.is_empty() {
+        Check::fail(
+            "Ports",
+            format!("{} ports of stopped servers are taken", failures.len()),
+        )
+        .details(failures)
+        .details(warnings)
+    } else if !warnings.is_empty() {
+        Check::warn(
+            "Ports",
+            format!("{} ports are shared by multiple servers", warnings.len()),
+        )
+        .details(warnings)
+    } else {
+        Check::pass("Ports", "No port conflicts")
+    })
+}
+
+/// Anonymous volumes without a container, e.g. left by removed servers
+///
+/// The engine does not remember which container created an anonymous volume,
+/// so these can just as well be left by other tools.
+async fn volumes(host: &Host) -> Result<Check> {
+    let volumes = host.manager.dangling_volumes().await?;
+    // Volumes declared by images get a random id as name
+    let anonymous = volumes
+        .iter()
+        .filter(|volume| {
+            volume.name.len() == 64 && volume.name.chars().all(|c| c.is_ascii_hexdigit())
+        })
+        .count();
+    if anonymous == 0 {
+        return Ok(Check::pass("Volumes", "No dangling anonymous volumes"));
+    }
+    Ok(Check::warn(
+        "Volumes",
+        format!(
+            "{} dangling anonymous volumes, e.g. of removed servers or other tools",
+            anonymous
+        ),
+    )
+    .details(Some(
+        "Remove them with `docker volume prune` after checking nothing needed is in them"
+            .to_string(),
+    )))
+}
+
+/// Whether completions are installed for the shell from `$SHELL`
+fn completions() -> Check {
+    const NAME: &str = "Completions";
+    let shell_path = env::var("SHELL").unwrap_or_default();
+    let shell_name = shell_path.rsplit('/').next().unwrap_or_default();
+    let shell = match shell_name {
+        "bash" => ShellType::Bash,
+        "fish" => ShellType::Fish,
+        "zsh" => ShellType::Zsh,
+        "" => return Check::warn(NAME, "Unable to detect the shell, `$SHELL` is not set"),
+        _ => return Check::pass(NAME, format!("Not checked for `{}`", shell_name)),
+    };
+    let file_name = shell.file_name("dgs");
+    // Zsh has no default directory for completions of a single user
+    let user = match shell {
+        ShellType::Zsh => None,
+        _ => Some(shell.user_path()),
+    };
+    match user
+        .into_iter()
+        .chain(Some(shell.system_path()))
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.exists())
+    {
+        Some(path) => Check::pass(
+            NAME,
+            format!("Installed for {} at `{}`", shell_name, path.display()),
+        ),
+        None => Check::warn(NAME, format!("Not installed for {}", shell_name)).details(Some(
+            match shell {
+                ShellType::Zsh => "Install them with `dgs completions zsh --system`".to_string(),
+                _ => format!("Install them with `dgs completions {}`", shell_name),
+            },
+        )),
+    }
+}
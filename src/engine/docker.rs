@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use bollard::container::{
+    AttachContainerOptions, Config, CreateContainerOptions, InspectContainerOptions,
+    ListContainersOptions, LogsOptions, RenameContainerOptions, StartContainerOptions,
+    StatsOptions, UploadToContainerOptions,
+};
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use bollard::image::{CreateImageOptions, ListImagesOptions};
+use bollard::models::{
+    ContainerInspectResponse, ContainerSummaryInner, CreateImageInfo, Image, ImageSummary,
+    SystemEventsResponse, Volume,
+};
+use bollard::system::EventsOptions;
+use bollard::volume::ListVolumesOptions;
+use bollard::Docker;
+use futures_util::stream::{BoxStream, StreamExt, TryStreamExt};
+
+use super::{Engine, EngineVersion, Stdin};
+use crate::resources::Usage;
+
+#[async_trait]
+impl Engine for Docker {
+    async fn list(
+        &self,
+        filters: HashMap<String, Vec<String>>,
+    ) -> Result<Vec<ContainerSummaryInner>> {
+        Ok(self
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters,
+                ..Default::default()
+            }))
+            .await?)
+    }
+
+    async fn create(&self, name: &str, config: Config<String>) -> Result<String> {
+        Ok(self
+            .create_container(Some(CreateContainerOptions { name }), config)
+            .await?
+            .id)
+    }
+
+    async fn start(&self, id: &str) -> Result<()> {
+        let options = Some(StartContainerOptions { detach_keys: "" });
+        Ok(self.start_container(id, options).await?)
+    }
+
+    async fn stop(&self, id: &str) -> Result<()> {
+        Ok(self.stop_container(id, None).await?)
+    }
+
+    async fn remove(&self, id: &str) -> Result<()> {
+        Ok(self.remove_container(id, None).await?)
+    }
+
+    async fn rename(&self, id: &str, name: &str) -> Result<()> {
+        Ok(self
+            .rename_container(id, RenameContainerOptions { name })
+            .await?)
+    }
+
+    fn pull(&self, options: CreateImageOptions<String>) -> BoxStream<'_, Result<CreateImageInfo>> {
+        self.create_image(Some(options), None, None)
+            .err_into()
+            .boxed()
+    }
+
+    async fn inspect(&self, id: &str) -> Result<ContainerInspectResponse> {
+        Ok(self
+            .inspect_container(id, None::<InspectContainerOptions>)
+            .await?)
+    }
+
+    fn logs(&self, id: &str) -> BoxStream<'_, Result<String>> {
+        Docker::logs(
+            self,
+            id,
+            Some(LogsOptions::<String> {
+                stdout: true,
+                stderr: true,
+                tail: "all".into(),
+                ..Default::default()
+            }),
+        )
+        .map_ok(|line| line.to_string())
+        .err_into()
+        .boxed()
+    }
+
+    fn events(
+        &self,
+        filters: HashMap<String, Vec<String>>,
+    ) -> BoxStream<'_, Result<SystemEventsResponse>> {
+        Docker::events(
+            self,
+            Some(EventsOptions {
+                filters,
+                ..Default::default()
+            }),
+        )
+        .err_into()
+        .boxed()
+    }
+
+    async fn exec(&self, id: &str, cmd: Vec<String>) -> Result<String> {
+        let exec_id = self
+            .create_exec(
+                id,
+                CreateExecOptions {
+                    cmd: Some(cmd),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .id;
+        let mut output = String::new();
+        if let StartExecResults::Attached { output: stream, .. } =
+            self.start_exec(&exec_id, None).await?
+        {
+            stream
+                .try_for_each(|log| {
+                    output += &log.to_string();
+                    async { Ok(()) }
+                })
+                .await?;
+        }
+        match self.inspect_exec(&exec_id).await?.exit_code {
+            Some(0) | None => Ok(output),
+            Some(code) => bail!("Command failed with exit code {}: {}", code, output.trim()),
+        }
+    }
+
+    async fn upload(&self, id: &str, archive: Vec<u8>) -> Result<()> {
+        Ok(self
+            .upload_to_container(
+                id,
+                Some(UploadToContainerOptions {
+                    path: "/",
+                    ..Default::default()
+                }),
+                archive.into(),
+            )
+            .await?)
+    }
+
+    async fn stats(&self, id: &str) -> Result<Usage> {
+        // Without `one_shot` the engine waits for a second sample to calculate the CPU usage
+        Box::pin(Docker::stats(
+            self,
+            id,
+            Some(StatsOptions {
+                stream: false,
+                one_shot: false,
+            }),
+        ))
+        .try_next()
+        .await?
+        .map(Usage::from)
+        .ok_or_else(|| anyhow!("The engine did not return any statistics"))
+    }
+
+    async fn attach_stdin(&self, id: &str) -> Result<Stdin> {
+        Ok(self
+            .attach_container(
+                id,
+                Some(AttachContainerOptions::<String> {
+                    stdin: Some(true),
+                    stream: Some(true),
+                    ..Default::default()
+                }),
+            )
+            .await?
+            .input)
+    }
+
+    async fn inspect_image(&self, image: &str) -> Result<Image> {
+        Ok(Docker::inspect_image(self, image).await?)
+    }
+
+    async fn images(&self) -> Result<Vec<ImageSummary>> {
+        Ok(self
+            .list_images(Some(ListImagesOptions::<String>::default()))
+            .await?)
+    }
+
+    async fn volumes(&self, filters: HashMap<String, Vec<String>>) -> Result<Vec<Volume>> {
+        Ok(self
+            .list_volumes(Some(ListVolumesOptions { filters }))
+            .await?
+            .volumes)
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Docker::ping(self).await?;
+        Ok(())
+    }
+
+    async fn version(&self) -> Result<EngineVersion> {
+        let version = Docker::version(self).await?;
+        Ok(EngineVersion {
+            version: version.version,
+            api_version: version.api_version,
+            components: version
+                .components
+                .into_iter()
+                .flatten()
+                .map(|component| component.name)
+                .collect(),
+        })
+    }
+}
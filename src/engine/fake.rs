@@ -0,0 +1,518 @@
+//! An engine keeping its containers in memory
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use bollard::container::Config;
+use bollard::image::CreateImageOptions;
+use bollard::models::{
+    ContainerConfig, ContainerInspectResponse, ContainerState, ContainerStateStatusEnum,
+    ContainerSummaryInner, CreateImageInfo, Health, HealthStatusEnum, Image, ImageSummary,
+    MountPoint, Port, PortTypeEnum, SystemEventsResponse, Volume,
+};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use tokio::io::AsyncWrite;
+
+use super::{Engine, EngineVersion, Stdin};
+use crate::image_repository;
+use crate::resources::Usage;
+
+#[derive(Clone, Debug)]
+pub struct FakeContainer {
+    pub id: String,
+    pub name: String,
+    pub config: Config<String>,
+    pub state: ContainerStateStatusEnum,
+    /// Only set for running containers with a health check
+    pub health: Option<HealthStatusEnum>,
+    /// Every archive uploaded into the container
+    pub uploads: Vec<Vec<u8>>,
+    /// Reported while the container is running
+    pub usage: Usage,
+    /// Everything written to the stdin of the container
+    pub stdin: Arc<Mutex<Vec<u8>>>,
+}
+
+impl FakeContainer {
+    pub fn new(name: &str, config: Config<String>) -> Self {
+        Self {
+            id: String::new(),
+            name: name.into(),
+            config,
+            state: ContainerStateStatusEnum::CREATED,
+            health: None,
+            uploads: Vec::new(),
+            usage: Usage::default(),
+            stdin: Arc::default(),
+        }
+    }
+
+    /// Volumes and paths in the `binds` of the container, like the engine mounts them
+    fn mounts(&self) -> Vec<MountPoint> {
+        self.config
+            .host_config
+            .iter()
+            .flat_map(|host_config| host_config.binds.iter().flatten())
+            .filter_map(|bind| {
+                let mut parts = bind.split(':');
+                let (source, destination) = (parts.next()?, parts.next()?);
+                let bind_mount = source.starts_with('/');
+                Some(MountPoint {
+                    typ: Some(if bind_mount { "bind" } else { "volume" }.into()),
+                    name: (!bind_mount).then(|| source.to_string()),
+                    source: Some(source.into()),
+                    destination: Some(destination.into()),
+                    rw: Some(parts.next() != Some("ro")),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    fn summary(&self) -> ContainerSummaryInner {
+        let ports = self
+            .config
+            .host_config
+            .iter()
+            .flat_map(|host_config| host_config.port_bindings.iter().flatten())
+            .filter_map(|(port, bindings)| {
+                let (private, typ) = port.split_once('/')?;
+                let binding = bindings.as_ref()?.first()?;
+                Some(Port {
+                    ip: binding.host_ip.clone(),
+                    private_port: private.parse().ok()?,
+                    public_port: binding.host_port.as_ref()?.parse().ok(),
+                    typ: typ.parse::<PortTypeEnum>().ok(),
+                })
+            })
+            .collect();
+        let health = match self.health {
+            Some(HealthStatusEnum::HEALTHY) => " (healthy)",
+            Some(HealthStatusEnum::UNHEALTHY) => " (unhealthy)",
+            Some(HealthStatusEnum::STARTING) => " (health: starting)",
+            _ => "",
+        };
+        ContainerSummaryInner {
+            id: Some(self.id.clone()),
+            names: Some(vec![format!("/{}", self.name)]),
+            image: self.config.image.clone(),
+            labels: Some(self.config.labels.clone().unwrap_or_default()),
+            ports: Some(ports),
+            state: Some(self.state.to_string()),
+            status: Some(match self.state {
+                ContainerStateStatusEnum::RUNNING => format!("Up 1 second{}", health),
+                ContainerStateStatusEnum::EXITED => "Exited (0) 1 second ago".into(),
+                _ => "Created".into(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// The config as returned by inspect
+    fn container_config(&self) -> ContainerConfig {
+        let config = self.config.clone();
+        ContainerConfig {
+            hostname: config.hostname,
+            domainname: config.domainname,
+            user: config.user,
+            attach_stdin: config.attach_stdin,
+            attach_stdout: config.attach_stdout,
+            attach_stderr: config.attach_stderr,
+            exposed_ports: config.exposed_ports,
+            tty: config.tty,
+            open_stdin: config.open_stdin,
+            stdin_once: config.stdin_once,
+            env: config.env,
+            cmd: config.cmd,
+            healthcheck: config.healthcheck,
+            args_escaped: config.args_escaped,
+            image: config.image,
+            volumes: config.volumes,
+            working_dir: config.working_dir,
+            entrypoint: config.entrypoint,
+            network_disabled: config.network_disabled,
+            mac_address: config.mac_address,
+            on_build: config.on_build,
+            labels: config.labels,
+            stop_signal: config.stop_signal,
+            stop_timeout: config.stop_timeout,
+            shell: config.shell,
+        }
+    }
+
+    /// Whether the container matches all engine `filters`
+    fn matches(&self, filters: &HashMap<String, Vec<String>>) -> bool {
+        let labels = self.config.labels.clone().unwrap_or_default();
+        filters.iter().all(|(filter, values)| {
+            values.iter().all(|value| match &**filter {
+                "label" => match value.split_once('=') {
+                    Some((key, value)) => labels.get(key).map(|v| v == value).unwrap_or(false),
+                    None => labels.contains_key(value),
+                },
+                "ancestor" => self
+                    .config
+                    .image
+                    .as_deref()
+                    .map(|image| image_repository(image) == image_repository(value))
+                    .unwrap_or(false),
+                "status" => self.state.to_string() == *value,
+                _ => true,
+            })
+        })
+    }
+}
+
+/// Appends everything written to the buffer of a [`FakeContainer`]
+struct FakeStdin(Arc<Mutex<Vec<u8>>>);
+
+impl AsyncWrite for FakeStdin {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A container engine without a daemon
+///
+/// Containers never run anything, servers with a health check report the
+/// health set with [`FakeEngine::with_health`] once started.
+pub struct FakeEngine {
+    containers: Mutex<Vec<FakeContainer>>,
+    pulled: Mutex<Vec<String>>,
+    images: Mutex<Vec<Image>>,
+    volumes: Mutex<Vec<Volume>>,
+    next_id: AtomicUsize,
+    health: HealthStatusEnum,
+    fail_create: AtomicBool,
+}
+
+impl Default for FakeEngine {
+    fn default() -> Self {
+        Self {
+            containers: Mutex::default(),
+            pulled: Mutex::default(),
+            images: Mutex::default(),
+            volumes: Mutex::default(),
+            next_id: AtomicUsize::new(1),
+            health: HealthStatusEnum::HEALTHY,
+            fail_create: AtomicBool::new(false),
+        }
+    }
+}
+
+impl FakeEngine {
+    /// The health started servers report
+    pub fn with_health(mut self, health: HealthStatusEnum) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Makes the next `create` fail, e.g. to test restoring recreated servers
+    pub fn fail_next_create(&self) {
+        self.fail_create.store(true, Ordering::SeqCst);
+    }
+
+    /// Adds a container as if it was created outside of dgs and returns its id
+    pub fn insert(&self, mut container: FakeContainer) -> String {
+        container.id = format!("{:064x}", self.next_id.fetch_add(1, Ordering::SeqCst));
+        let id = container.id.clone();
+        self.containers.lock().unwrap().push(container);
+        id
+    }
+
+    pub fn containers(&self) -> Vec<FakeContainer> {
+        self.containers.lock().unwrap().clone()
+    }
+
+    /// Adds an image as if it was pulled, e.g. to set the defaults of its config
+    pub fn insert_image(&self, image: Image) {
+        self.images.lock().unwrap().push(image);
+    }
+
+    /// Adds a volume as if it was created outside of dgs
+    pub fn insert_volume(&self, volume: Volume) {
+        self.volumes.lock().unwrap().push(volume);
+    }
+
+    /// Every image pulled so far e.g. `docker.io/itzg/minecraft-server:latest`
+    pub fn pulled(&self) -> Vec<String> {
+        self.pulled.lock().unwrap().clone()
+    }
+
+    fn with_container<T>(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut FakeContainer) -> Result<T>,
+    ) -> Result<T> {
+        let mut containers = self.containers.lock().unwrap();
+        let container = containers
+            .iter_mut()
+            .find(|container| container.id == id || container.name == id)
+            .ok_or_else(|| anyhow!("No such container: {}", id))?;
+        f(container)
+    }
+}
+
+#[async_trait]
+impl Engine for FakeEngine {
+    async fn list(
+        &self,
+        filters: HashMap<String, Vec<String>>,
+    ) -> Result<Vec<ContainerSummaryInner>> {
+        Ok(self
+            .containers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|container| container.matches(&filters))
+            .map(FakeContainer::summary)
+            .collect())
+    }
+
+    async fn create(&self, name: &str, config: Config<String>) -> Result<String> {
+        if self.fail_create.swap(false, Ordering::SeqCst) {
+            bail!("No space left on device");
+        }
+        if self
+            .containers
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|container| container.name == name)
+        {
+            bail!("Conflict. The container name `/{}` is already in use", name);
+        }
+        Ok(self.insert(FakeContainer::new(name, config)))
+    }
+
+    async fn start(&self, id: &str) -> Result<()> {
+        let health = self.health;
+        self.with_container(id, |container| {
+            container.state = ContainerStateStatusEnum::RUNNING;
+            container.health = container.config.healthcheck.as_ref().map(|_| health);
+            Ok(())
+        })
+    }
+
+    async fn stop(&self, id: &str) -> Result<()> {
+        self.with_container(id, |container| {
+            container.state = ContainerStateStatusEnum::EXITED;
+            container.health = None;
+            Ok(())
+        })
+    }
+
+    async fn remove(&self, id: &str) -> Result<()> {
+        self.with_container(id, |container| {
+            if container.state == ContainerStateStatusEnum::RUNNING {
+                bail!("You cannot remove a running container {}", id);
+            }
+            Ok(())
+        })?;
+        self.containers
+            .lock()
+            .unwrap()
+            .retain(|container| container.id != id && container.name != id);
+        Ok(())
+    }
+
+    async fn rename(&self, id: &str, name: &str) -> Result<()> {
+        if self
+            .containers
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|container| container.name == name)
+        {
+            bail!("Conflict. The container name `/{}` is already in use", name);
+        }
+        self.with_container(id, |container| {
+            container.name = name.into();
+            Ok(())
+        })
+    }
+
+    fn pull(&self, options: CreateImageOptions<String>) -> BoxStream<'_, Result<CreateImageInfo>> {
+        let tag = if options.tag.is_empty() {
+            "latest"
+        } else {
+            &options.tag
+        };
+        let reference = format!("{}:{}", options.from_image, tag);
+        self.pulled.lock().unwrap().push(reference.clone());
+        let id = format!(
+            "sha256:{:064x}",
+            self.next_id.fetch_add(1, Ordering::SeqCst)
+        );
+        self.insert_image(Image {
+            repo_digests: Some(vec![format!("{}@{}", image_repository(&reference), id)]),
+            repo_tags: Some(vec![reference]),
+            id,
+            ..Default::default()
+        });
+        stream::once(async {
+            Ok(CreateImageInfo {
+                status: Some("Downloaded newer image".into()),
+                ..Default::default()
+            })
+        })
+        .boxed()
+    }
+
+    async fn inspect(&self, id: &str) -> Result<ContainerInspectResponse> {
+        self.with_container(id, |container| {
+            Ok(ContainerInspectResponse {
+                id: Some(container.id.clone()),
+                name: Some(format!("/{}", container.name)),
+                image: container.config.image.clone(),
+                state: Some(ContainerState {
+                    status: Some(container.state),
+                    running: Some(container.state == ContainerStateStatusEnum::RUNNING),
+                    health: container.health.map(|status| Health {
+                        status: Some(status),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                config: Some(container.container_config()),
+                host_config: container.config.host_config.clone(),
+                mounts: Some(container.mounts()),
+                ..Default::default()
+            })
+        })
+    }
+
+    fn logs(&self, _id: &str) -> BoxStream<'_, Result<String>> {
+        stream::empty().boxed()
+    }
+
+    fn events(
+        &self,
+        _filters: HashMap<String, Vec<String>>,
+    ) -> BoxStream<'_, Result<SystemEventsResponse>> {
+        stream::empty().boxed()
+    }
+
+    async fn exec(&self, id: &str, _cmd: Vec<String>) -> Result<String> {
+        self.with_container(id, |container| {
+            if container.state != ContainerStateStatusEnum::RUNNING {
+                bail!("Container {} is not running", id);
+            }
+            Ok(String::new())
+        })
+    }
+
+    async fn upload(&self, id: &str, archive: Vec<u8>) -> Result<()> {
+        self.with_container(id, |container| {
+            container.uploads.push(archive);
+            Ok(())
+        })
+    }
+
+    async fn stats(&self, id: &str) -> Result<Usage> {
+        self.with_container(id, |container| {
+            if container.state != ContainerStateStatusEnum::RUNNING {
+                bail!("Container {} is not running", id);
+            }
+            Ok(container.usage)
+        })
+    }
+
+    async fn attach_stdin(&self, id: &str) -> Result<Stdin> {
+        let stdin = self.with_container(id, |container| Ok(container.stdin.clone()))?;
+        Ok(Box::pin(FakeStdin(stdin)))
+    }
+
+    /// Tags of the image are matched by repository only
+    async fn inspect_image(&self, image: &str) -> Result<Image> {
+        self.images
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|candidate| {
+                candidate.id == image
+                    || candidate
+                        .repo_tags
+                        .iter()
+                        .flatten()
+                        .any(|tag| image_repository(tag) == image_repository(image))
+            })
+            .cloned()
+            .ok_or_else(|| anyhow!("No such image: {}", image))
+    }
+
+    async fn images(&self) -> Result<Vec<ImageSummary>> {
+        Ok(self
+            .images
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|image| ImageSummary {
+                id: image.id.clone(),
+                repo_tags: image.repo_tags.clone().unwrap_or_default(),
+                repo_digests: image.repo_digests.clone().unwrap_or_default(),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    /// Supports the `dangling` and `label` filters
+    async fn volumes(&self, filters: HashMap<String, Vec<String>>) -> Result<Vec<Volume>> {
+        let used: Vec<String> = self
+            .containers()
+            .iter()
+            .flat_map(FakeContainer::mounts)
+            .filter_map(|mount| mount.name)
+            .collect();
+        Ok(self
+            .volumes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|volume| {
+                filters.iter().all(|(filter, values)| {
+                    values.iter().all(|value| match &**filter {
+                        "dangling" => (value == "true") != used.contains(&volume.name),
+                        "label" => match value.split_once('=') {
+                            Some((key, value)) => {
+                                volume.labels.get(key).map(String::as_str) == Some(value)
+                            }
+                            None => volume.labels.contains_key(value),
+                        },
+                        _ => true,
+                    })
+                })
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn version(&self) -> Result<EngineVersion> {
+        Ok(EngineVersion {
+            version: Some("20.10.8".into()),
+            api_version: Some("1.41".into()),
+            components: vec!["Engine".into()],
+        })
+    }
+}
@@ -0,0 +1,75 @@
+//! The container engine calls dgs relies on
+//!
+//! [`Docker`](bollard::Docker) implements [`Engine`] for real engines, [`fake::FakeEngine`]
+//! keeps everything in memory for tests.
+use std::collections::HashMap;
+use std::pin::Pin;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bollard::container::Config;
+use bollard::image::CreateImageOptions;
+use bollard::models::{
+    ContainerInspectResponse, ContainerSummaryInner, CreateImageInfo, Image, ImageSummary,
+    SystemEventsResponse, Volume,
+};
+use futures_util::stream::BoxStream;
+use tokio::io::AsyncWrite;
+
+use crate::resources::Usage;
+
+mod docker;
+pub mod fake;
+
+/// The stdin of a container
+pub type Stdin = Pin<Box<dyn AsyncWrite + Send>>;
+
+/// What an engine reports about itself
+#[derive(Clone, Debug, Default)]
+pub struct EngineVersion {
+    pub version: Option<String>,
+    pub api_version: Option<String>,
+    /// e.g. `Engine` for Docker or `Podman Engine`
+    pub components: Vec<String>,
+}
+
+#[async_trait]
+pub trait Engine: Send + Sync {
+    /// All containers, including stopped ones, matching the engine `filters`
+    async fn list(
+        &self,
+        filters: HashMap<String, Vec<String>>,
+    ) -> Result<Vec<ContainerSummaryInner>>;
+    /// Creates a container and returns its id
+    async fn create(&self, name: &str, config: Config<String>) -> Result<String>;
+    async fn start(&self, id: &str) -> Result<()>;
+    async fn stop(&self, id: &str) -> Result<()>;
+    async fn remove(&self, id: &str) -> Result<()>;
+    async fn rename(&self, id: &str, name: &str) -> Result<()>;
+    fn pull(&self, options: CreateImageOptions<String>) -> BoxStream<'_, Result<CreateImageInfo>>;
+    async fn inspect(&self, id: &str) -> Result<ContainerInspectResponse>;
+    /// The output of the container so far, one item per line
+    fn logs(&self, id: &str) -> BoxStream<'_, Result<String>>;
+    /// Events matching the engine `filters`
+    fn events(
+        &self,
+        filters: HashMap<String, Vec<String>>,
+    ) -> BoxStream<'_, Result<SystemEventsResponse>>;
+    /// Runs `cmd` in the container and returns its output, failing on a non zero exit code
+    async fn exec(&self, id: &str, cmd: Vec<String>) -> Result<String>;
+    /// Extracts the tar `archive` into the root of the container
+    async fn upload(&self, id: &str, archive: Vec<u8>) -> Result<()>;
+    /// The current resource usage of a running container
+    async fn stats(&self, id: &str) -> Result<Usage>;
+    /// Writes to the stdin of a container started with `open_stdin`
+    async fn attach_stdin(&self, id: &str) -> Result<Stdin>;
+    /// An image by id or reference e.g. `itzg/minecraft-server:latest`
+    async fn inspect_image(&self, image: &str) -> Result<Image>;
+    /// All images that are pulled
+    async fn images(&self) -> Result<Vec<ImageSummary>>;
+    /// Volumes matching the engine `filters`
+    async fn volumes(&self, filters: HashMap<String, Vec<String>>) -> Result<Vec<Volume>>;
+    /// Fails if the engine does not answer
+    async fn ping(&self) -> Result<()>;
+    async fn version(&self) -> Result<EngineVersion>;
+}
@@ -0,0 +1,123 @@
+//! Failures callers might want to react to
+//!
+//! Everything else is reported as a plain [`anyhow::Error`], these can be found
+//! with [`anyhow::Error::downcast_ref`]. `dgs` exits with the following codes:
+//!
+//! | Code | Failure                      |
+//! |------|------------------------------|
+//! | 0    | Success                      |
+//! | 1    | Any other error              |
+//! | 2    | [`Error::InvalidArguments`]  |
+//! | 3    | [`Error::EngineUnreachable`] |
+//! | 4    | [`Error::PullFailed`]        |
+//! | 5    | [`Error::PortUnavailable`]   |
+//! | 6    | [`Error::NotFound`]          |
+//! | 7    | [`Error::AmbiguousName`]     |
+//! | 8    | [`Error::Unsupported`]       |
+//! | 9    | [`Error::NotReady`]          |
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use crate::GameName;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Arguments clap accepted that do not fit together
+    InvalidArguments(String),
+    /// The container engine did not answer
+    EngineUnreachable(String),
+    /// The image of a game could not be pulled
+    PullFailed { image: String, reason: String },
+    /// A port the server needs on the host is taken
+    PortUnavailable(String),
+    /// There is no server with this name
+    NotFound(String),
+    /// The name matches more than one server
+    AmbiguousName { name: String, matches: Vec<String> },
+    /// The game does not support the feature e.g. `RCON`
+    Unsupported { game: GameName, feature: String },
+    /// The server was still starting when the time to wait for it ran out
+    NotReady(Duration),
+}
+
+impl Error {
+    pub fn unsupported(game: GameName, feature: impl Into<String>) -> Self {
+        Error::Unsupported {
+            game,
+            feature: feature.into(),
+        }
+    }
+
+    /// The exit code of `dgs` for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidArguments(_) => 2,
+            Error::EngineUnreachable(_) => 3,
+            Error::PullFailed { .. } => 4,
+            Error::PortUnavailable(_) => 5,
+            Error::NotFound(_) => 6,
+            Error::AmbiguousName { .. } => 7,
+            Error::Unsupported { .. } => 8,
+            Error::NotReady(_) => 9,
+        }
+    }
+
+    /// What the user could do about it
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Error::InvalidArguments(_) => Some("See the usage with `--help`"),
+            Error::EngineUnreachable(_) => Some(
+                "Make sure Docker is running, use `--podman-user` or `--podman-system` for Podman \
+                 and check the `uri` of hosts in the config",
+            ),
+            Error::PullFailed { .. } => {
+                Some("Check your internet connection and that the `--version` exists")
+            }
+            Error::PortUnavailable(_) => {
+                Some("Stop the server or program using the port on the host")
+            }
+            Error::NotFound(_) => {
+                Some("Check the name with `dgs server ls`, servers on other hosts need `--host`")
+            }
+            Error::AmbiguousName { .. } => Some("Use the full name of the server"),
+            Error::Unsupported { .. } => None,
+            Error::NotReady(_) => Some(
+                "Wait longer with `--wait-timeout`, some games take minutes to create their world",
+            ),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidArguments(reason) => write!(f, "Invalid arguments: {}", reason),
+            Error::EngineUnreachable(reason) => {
+                write!(f, "Unable to connect to the container engine: {}", reason)
+            }
+            Error::PullFailed { image, reason } => {
+                write!(f, "Unable to pull `{}`: {}", image, reason)
+            }
+            Error::PortUnavailable(reason) => write!(f, "Port unavailable: {}", reason),
+            Error::NotFound(name) => write!(f, "There is no server matching `{}`", name),
+            Error::AmbiguousName { name, matches } => write!(
+                f,
+                "`{}` matches multiple servers: {}",
+                name,
+                matches
+                    .iter()
+                    .map(|server| format!("`{}`", server))
+                    .intersperse(", ".to_owned())
+                    .collect::<String>()
+            ),
+            Error::Unsupported { game, feature } => {
+                write!(f, "{} does not support {}", game, feature)
+            }
+            Error::NotReady(time_out) => {
+                write!(f, "The server was not ready after {}s", time_out.as_secs())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,75 @@
+use std::ops::Deref;
+use std::str::FromStr;
+
+use bollard::models::ContainerStateStatusEnum;
+use clap::Clap;
+
+use crate::GameName;
+
+/// Which servers to return from [`ServerManager::list`](crate::ServerManager::list)
+#[derive(Clap, Clone, Default)]
+pub struct ServerFilter {
+    /// Only servers matching the name will be returned.
+    #[clap(short, long)]
+    pub name: Option<String>,
+    /// Only servers with a matching game name will be returned.
+    #[clap(short, long, arg_enum)]
+    pub game: Option<GameName>,
+    /// Only servers with these tags (case is ignored) will be returned.
+    ///
+    /// Usage: `-t first_tag -t second_tag`.
+    /// This would return all servers that have both `first_tag` and `second_tag`.
+    #[clap(short, long = "tag")]
+    pub tags: Vec<LowerCaseString>,
+    /// Only servers with this state are returned
+    #[clap(short, long)]
+    pub state: Option<ContainerStateStatusEnum>,
+}
+
+#[derive(Clone)]
+pub struct LowerCaseString(String);
+
+impl FromStr for LowerCaseString {
+    type Err = !;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_lowercase()))
+    }
+}
+
+impl From<&str> for LowerCaseString {
+    fn from(s: &str) -> Self {
+        Self(s.into())
+    }
+}
+
+impl From<String> for LowerCaseString {
+    fn from(s: String) -> Self {
+        Self(s)
+    }
+}
+
+impl From<LowerCaseString> for String {
+    fn from(lcs: LowerCaseString) -> Self {
+        lcs.0
+    }
+}
+
+impl Deref for LowerCaseString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PartialEq<String> for LowerCaseString {
+    fn eq(&self, other: &String) -> bool {
+        *other == self.0
+    }
+}
+impl PartialEq<String> for &LowerCaseString {
+    fn eq(&self, other: &String) -> bool {
+        *other == self.0
+    }
+}
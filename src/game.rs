@@ -0,0 +1,598 @@
+//! The games dgs can run and how their images are configured
+use core::fmt;
+use std::fmt::Display;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+use bollard::models::{HealthConfig, PortTypeEnum};
+use clap::ArgEnum;
+
+use crate::resources::{Limits, Memory};
+
+#[derive(Debug, Clone)]
+pub enum PortConfiguration {
+    /// Published on the same ports on the host, as the game announces them to clients
+    NonConfigurable(&'static [(u16, PortTypeEnum)]),
+    /// Published on free ports picked on the host
+    Configurable(&'static [(u16, PortTypeEnum)]),
+}
+
+impl PortConfiguration {
+    pub fn ports(&self) -> &'static [(u16, PortTypeEnum)] {
+        match self {
+            PortConfiguration::NonConfigurable(ports) | PortConfiguration::Configurable(ports) => {
+                ports
+            }
+        }
+    }
+}
+
+/// How dgs can ask a running server for its status
+#[derive(Debug, Clone, Copy)]
+pub enum QueryProtocol {
+    /// Minecraft Server List Ping on this TCP port in the container
+    MinecraftPing(u16),
+    /// Valve A2S on this UDP port in the container
+    A2s(u16),
+}
+
+/// How RCON is enabled for a game
+#[derive(Debug, Clone)]
+pub struct RconConfiguration {
+    /// TCP port in the container
+    pub port: u16,
+    /// Envs enabling RCON, `{password}` is replaced with the generated password
+    pub envs: &'static [&'static str],
+    /// File in the container the game reads the password from
+    pub password_file: Option<&'static str>,
+}
+
+/// How `dgs server cmd` delivers console commands to a game
+#[derive(Debug, Clone, Copy)]
+pub enum CommandDelivery {
+    /// Run this command in the container with the line appended
+    Exec(&'static [&'static str]),
+    /// Use the RCON connection dgs configures on creation
+    Rcon,
+    /// Write the line to the stdin of the server
+    Stdin,
+}
+
+/// How `dgs server players` manages the players of a game
+#[derive(Debug, Clone)]
+pub enum PlayerManagement {
+    /// Console commands sent like `dgs server cmd`, `{player}` is replaced
+    Commands {
+        whitelist: ListCommands,
+        ops: ListCommands,
+        bans: ListCommands,
+        kick: &'static str,
+    },
+    /// Files in the container with one player per line
+    Files {
+        whitelist: &'static str,
+        ops: &'static str,
+        bans: &'static str,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ListCommands {
+    pub add: &'static str,
+    pub remove: &'static str,
+    /// Not every game can print every list
+    pub list: Option<&'static str>,
+}
+
+/// Envs taking comma separated players on creation
+#[derive(Debug, Clone)]
+pub struct PlayerEnvs {
+    pub whitelist: &'static str,
+    pub ops: &'static str,
+}
+
+/// Label the generated RCON password is stored in
+pub const RCON_PASSWORD_LABEL: &str = "dgs.rcon-password";
+/// Label for the server software or modpack of games supporting more than one
+pub const SERVER_TYPE_LABEL: &str = "dgs.server-type";
+
+#[derive(Debug, Clone)]
+pub struct Version {
+    pub config: VersionConfiguration,
+    pub ls: VersionLs,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionConfiguration {
+    Tag,
+    Env(&'static str),
+    None,
+}
+#[derive(Debug, Clone)]
+pub enum VersionLs {
+    Help(&'static str),
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub name: GameName,
+    pub image: &'static str,
+    pub ports: PortConfiguration,
+    pub envs: &'static [&'static str],
+    /// License the user has to accept before dgs runs the game
+    pub license: Option<License>,
+    pub version: Version,
+    /// Defaults for options not specified on creation
+    pub limits: Limits,
+    /// Env telling the server how much memory it may use, set to the memory limit
+    pub memory_env: Option<&'static str>,
+    pub health_check: Option<HealthCheck>,
+    pub query: Option<QueryProtocol>,
+    pub rcon: Option<RconConfiguration>,
+    pub command_delivery: Option<CommandDelivery>,
+    pub players: Option<PlayerManagement>,
+    pub player_envs: Option<PlayerEnvs>,
+    /// Directory in the container the saves or worlds are stored in
+    pub saves: Option<&'static str>,
+}
+
+/// A license the image only accepts through an env
+#[derive(Debug, Clone)]
+pub struct License {
+    pub name: &'static str,
+    pub url: &'static str,
+    /// Env telling the image the license was accepted e.g. `EULA=TRUE`
+    pub env: &'static str,
+}
+
+/// A command run inside the container to check whether the game is ready
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    /// In the format of the Dockerfile `HEALTHCHECK` e.g. `["CMD", "mc-health"]`
+    pub test: &'static [&'static str],
+    /// Seconds the game may take to start before failed checks count
+    pub start_period: u64,
+}
+
+impl HealthCheck {
+    pub fn config(&self) -> HealthConfig {
+        const SECOND: i64 = 1_000_000_000;
+        HealthConfig {
+            test: Some(self.test.iter().map(|s| s.to_string()).collect()),
+            interval: Some(10 * SECOND),
+            timeout: Some(5 * SECOND),
+            retries: Some(3),
+            start_period: Some(self.start_period as i64 * SECOND),
+        }
+    }
+}
+
+/// The repository of an image reference without its tag, digest and the
+/// implicit `docker.io/` and `library/` prefixes
+///
+/// `docker.io/itzg/minecraft-server:java17` and `itzg/minecraft-server` are
+/// both `itzg/minecraft-server`.
+pub fn image_repository(reference: &str) -> &str {
+    let reference = reference
+        .split_once('@')
+        .map(|(reference, _)| reference)
+        .unwrap_or(reference);
+    // Registries can have a port, so only a colon after the last `/` is a tag
+    let reference = match reference.rsplit_once(':') {
+        Some((repository, tag)) if !tag.contains('/') => repository,
+        _ => reference,
+    };
+    let reference = reference.strip_prefix("docker.io/").unwrap_or(reference);
+    reference.strip_prefix("library/").unwrap_or(reference)
+}
+
+impl Game {
+    pub fn find_by_image(image_name: &str) -> Option<&'static Self> {
+        let image_name = image_repository(image_name);
+        GAMES
+            .iter()
+            .find(|Game { image, .. }| image_repository(image) == image_name)
+    }
+    /// Finds the game called `game_name` (case is ignored)
+    pub fn find_by_name(game_name: &str) -> Option<&'static Self> {
+        GAMES
+            .iter()
+            .find(|Game { name, .. }| name.eq_ignore_ascii_case(game_name))
+    }
+}
+
+impl FromStr for &Game {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Game::find_by_name(s).ok_or_else(|| anyhow!("Unable to find a game matching `{}`", s))
+    }
+}
+
+#[derive(Clone, Copy, Debug, ArgEnum, PartialEq)]
+pub enum GameName {
+    Minecraft,
+    Factorio,
+    Valheim,
+    Terraria,
+    Satisfactory,
+    #[clap(name = "7-days-to-die")]
+    SevenDaysToDie,
+    Cs2,
+    Palworld,
+}
+
+impl Display for GameName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", &**self)
+    }
+}
+
+impl Deref for GameName {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        use GameName::*;
+        match self {
+            Minecraft => "minecraft",
+            Factorio => "factorio",
+            Valheim => "valheim",
+            Terraria => "terraria",
+            Satisfactory => "satisfactory",
+            SevenDaysToDie => "7-days-to-die",
+            Cs2 => "cs2",
+            Palworld => "palworld",
+        }
+    }
+}
+
+/// Every game dgs can run
+pub const GAMES: &[Game] = &[
+    Game {
+        name: GameName::Minecraft,
+        image: "docker.io/itzg/minecraft-server",
+        ports: PortConfiguration::Configurable(&[(25565, PortTypeEnum::TCP)]),
+        envs: &[],
+        license: Some(License {
+            name: "Minecraft End User License Agreement",
+            url: "https://aka.ms/MinecraftEULA",
+            env: "EULA=TRUE",
+        }),
+        version: Version {
+            config: VersionConfiguration::Env("VERSION"),
+            ls: VersionLs::Help("You can either specify `LATEST` (the default) to run the latest stable version, `SNAPSHOT` to run the latest snapshot, or you can specify the version directly e.g. `1.7.2` or `21w11a` ")
+        },
+        limits: Limits {
+            memory: Some(Memory::gib(2)),
+            cpus: None,
+            restart: None,
+        },
+        memory_env: Some("MEMORY"),
+        health_check: Some(HealthCheck {
+            test: &["CMD", "mc-health"],
+            start_period: 60,
+        }),
+        query: Some(QueryProtocol::MinecraftPing(25565)),
+        rcon: Some(RconConfiguration {
+            port: 25575,
+            envs: &["ENABLE_RCON=true", "RCON_PASSWORD={password}"],
+            password_file: None,
+        }),
+        // Uses the RCON settings from the environment
+        command_delivery: Some(CommandDelivery::Exec(&["rcon-cli"])),
+        players: Some(PlayerManagement::Commands {
+            whitelist: ListCommands {
+                add: "whitelist add {player}",
+                remove: "whitelist remove {player}",
+                list: Some("whitelist list"),
+            },
+            ops: ListCommands {
+                add: "op {player}",
+                remove: "deop {player}",
+                list: None,
+            },
+            bans: ListCommands {
+                add: "ban {player}",
+                remove: "pardon {player}",
+                list: Some("banlist players"),
+            },
+            kick: "kick {player}",
+        }),
+        player_envs: Some(PlayerEnvs {
+            whitelist: "WHITELIST",
+            ops: "OPS",
+        }),
+        // Worlds are directories that cannot be switched without a restart
+        saves: None,
+    },
+    Game {
+        name: GameName::Factorio,
+        image: "docker.io/factoriotools/factorio",
+        ports: PortConfiguration::Configurable(&[(34197, PortTypeEnum::UDP)]),
+        envs: &[],
+        license: None,
+        version: Version {
+            config: VersionConfiguration::Tag,
+            ls: VersionLs::Help("You can either specify `latest` (the default) to run the latest (maybe experimental) version, `stable` to run the latest stable version, or you can specify the version directly e.g. `1.1` or `0.15.40`. You can also look for availible versions at https://hub.docker.com/r/factoriotools/factorio/tags.")
+        },
+        limits: Limits {
+            memory: None,
+            cpus: None,
+            restart: None,
+        },
+        memory_env: None,
+        // Checks that the game port (34197, `8595` in hex) is bound, the image ships no query tool
+        health_check: Some(HealthCheck {
+            test: &["CMD-SHELL", "grep -qi ':8595 ' /proc/net/udp /proc/net/udp6"],
+            start_period: 30,
+        }),
+        query: None,
+        rcon: Some(RconConfiguration {
+            port: 27015,
+            envs: &[],
+            password_file: Some("/factorio/config/rconpw"),
+        }),
+        command_delivery: Some(CommandDelivery::Rcon),
+        players: Some(PlayerManagement::Commands {
+            whitelist: ListCommands {
+                add: "/whitelist add {player}",
+                remove: "/whitelist remove {player}",
+                list: Some("/whitelist get"),
+            },
+            ops: ListCommands {
+                add: "/promote {player}",
+                remove: "/demote {player}",
+                list: Some("/admins"),
+            },
+            bans: ListCommands {
+                add: "/ban {player}",
+                remove: "/unban {player}",
+                list: Some("/banlist get"),
+            },
+            kick: "/kick {player}",
+        }),
+        player_envs: None,
+        saves: Some("/factorio/saves"),
+    },
+    Game {
+        name: GameName::Valheim,
+        image: "docker.io/lloesche/valheim-server",
+        ports: PortConfiguration::NonConfigurable(&[
+            (2456, PortTypeEnum::UDP),
+            (2457, PortTypeEnum::UDP),
+            (2458, PortTypeEnum::UDP),
+        ]),
+        envs: &[],
+        license: None,
+        version: Version {
+            config: VersionConfiguration::None,
+            ls: VersionLs::None
+        },
+        // The server needs 4GB according to lloesche/valheim-server, the CPUs are
+        // not limited as it needs at least 2 cores
+        limits: Limits {
+            memory: Some(Memory::gib(4)),
+            cpus: None,
+            restart: None,
+        },
+        memory_env: None,
+        // The query port (2457, `0999` in hex) is only bound once the world is loaded
+        health_check: Some(HealthCheck {
+            test: &["CMD-SHELL", "grep -qi ':0999 ' /proc/net/udp /proc/net/udp6"],
+            start_period: 300,
+        }),
+        query: Some(QueryProtocol::A2s(2457)),
+        rcon: None,
+        command_delivery: Some(CommandDelivery::Stdin),
+        // Players are identified by their SteamID64
+        players: Some(PlayerManagement::Files {
+            whitelist: "/config/permittedlist.txt",
+            ops: "/config/adminlist.txt",
+            bans: "/config/bannedlist.txt",
+        }),
+        player_envs: None,
+        saves: Some("/config/worlds_local"),
+    },
+    Game {
+        name: GameName::Terraria,
+        image: "docker.io/jacobsmile/tmodloader1.4",
+        ports: PortConfiguration::Configurable(&[(7777, PortTypeEnum::TCP)]),
+        envs: &[],
+        license: None,
+        version: Version {
+            config: VersionConfiguration::Tag,
+            ls: VersionLs::Help("You can either specify `latest` (the default) to run the latest tModLoader release, or you can specify the version directly e.g. `v2023.08.3.0`. You can also look for availible versions at https://hub.docker.com/r/jacobsmile/tmodloader1.4/tags.")
+        },
+        limits: Limits {
+            memory: None,
+            cpus: None,
+            restart: None,
+        },
+        memory_env: None,
+        // Checks that the game port (7777, `1E61` in hex) is bound
+        health_check: Some(HealthCheck {
+            test: &["CMD-SHELL", "grep -qi ':1E61 ' /proc/net/tcp /proc/net/tcp6"],
+            start_period: 120,
+        }),
+        query: None,
+        rcon: None,
+        // The image runs the server in tmux and ships a script to send lines to it
+        command_delivery: Some(CommandDelivery::Exec(&["inject"])),
+        players: None,
+        player_envs: None,
+        saves: Some("/data/tModLoader/Worlds"),
+    },
+    Game {
+        name: GameName::Satisfactory,
+        image: "docker.io/wolveix/satisfactory-server",
+        ports: PortConfiguration::NonConfigurable(&[
+            (7777, PortTypeEnum::UDP),
+            (7777, PortTypeEnum::TCP),
+            (8888, PortTypeEnum::TCP),
+        ]),
+        envs: &[],
+        license: None,
+        version: Version {
+            config: VersionConfiguration::None,
+            ls: VersionLs::None
+        },
+        // The image recommends 12GB, the server uses far more than the minimum of 8GB in later stages
+        limits: Limits {
+            memory: Some(Memory::gib(12)),
+            cpus: None,
+            restart: None,
+        },
+        memory_env: None,
+        // The server is downloaded on first start, which takes a while
+        health_check: Some(HealthCheck {
+            test: &["CMD-SHELL", "grep -qi ':1E61 ' /proc/net/udp /proc/net/udp6"],
+            start_period: 600,
+        }),
+        query: None,
+        rcon: None,
+        // Satisfactory is managed with its own client or HTTPS API instead of a console
+        command_delivery: None,
+        players: None,
+        player_envs: None,
+        saves: Some("/config/saved/server"),
+    },
+    Game {
+        name: GameName::SevenDaysToDie,
+        image: "docker.io/vinanrra/7dtd-server",
+        ports: PortConfiguration::NonConfigurable(&[
+            (26900, PortTypeEnum::TCP),
+            (26900, PortTypeEnum::UDP),
+            (26901, PortTypeEnum::UDP),
+            (26902, PortTypeEnum::UDP),
+        ]),
+        // Without a start mode the image only installs the server
+        envs: &["START_MODE=1"],
+        license: None,
+        version: Version {
+            config: VersionConfiguration::Env("VERSION"),
+            ls: VersionLs::Help("You can either specify `stable` (the default) to run the latest stable version, `latest_experimental` to run the latest experimental version, or you can specify a branch directly e.g. `alpha20.7`.")
+        },
+        limits: Limits {
+            memory: None,
+            cpus: None,
+            restart: None,
+        },
+        memory_env: None,
+        // Checks that the game port (26900, `6914` in hex) is bound
+        health_check: Some(HealthCheck {
+            test: &["CMD-SHELL", "grep -qi ':6914 ' /proc/net/tcp /proc/net/tcp6"],
+            start_period: 900,
+        }),
+        query: Some(QueryProtocol::A2s(26900)),
+        rcon: None,
+        // The console is only reachable via telnet
+        command_delivery: None,
+        players: None,
+        player_envs: None,
+        saves: Some("/home/sdtdserver/.local/share/7DaysToDie/Saves"),
+    },
+    Game {
+        name: GameName::Cs2,
+        image: "docker.io/joedwards32/cs2",
+        // The TCP game port is only used for RCON, which is published separately
+        ports: PortConfiguration::NonConfigurable(&[
+            (27015, PortTypeEnum::UDP),
+            (27020, PortTypeEnum::UDP),
+        ]),
+        envs: &[],
+        license: None,
+        version: Version {
+            config: VersionConfiguration::None,
+            ls: VersionLs::None
+        },
+        limits: Limits {
+            memory: None,
+            cpus: None,
+            restart: None,
+        },
+        memory_env: None,
+        // The game (about 30GB) is downloaded on first start
+        health_check: Some(HealthCheck {
+            test: &["CMD-SHELL", "grep -qi ':6987 ' /proc/net/udp /proc/net/udp6"],
+            start_period: 1800,
+        }),
+        query: Some(QueryProtocol::A2s(27015)),
+        rcon: Some(RconConfiguration {
+            port: 27015,
+            envs: &["CS2_RCONPW={password}"],
+            password_file: None,
+        }),
+        command_delivery: Some(CommandDelivery::Rcon),
+        // CS2 has no console commands for a whitelist or admins
+        players: None,
+        player_envs: None,
+        saves: None,
+    },
+    Game {
+        name: GameName::Palworld,
+        image: "docker.io/thijsvanloef/palworld-server-docker",
+        ports: PortConfiguration::Configurable(&[
+            (8211, PortTypeEnum::UDP),
+            (27015, PortTypeEnum::UDP),
+        ]),
+        envs: &[],
+        license: None,
+        version: Version {
+            config: VersionConfiguration::Tag,
+            ls: VersionLs::Help("You can either specify `latest` (the default) to run the latest version, or you can specify the image version directly e.g. `v0.39.1`. You can also look for availible versions at https://hub.docker.com/r/thijsvanloef/palworld-server-docker/tags.")
+        },
+        // The server leaks memory, 16GB are recommended by the image
+        limits: Limits {
+            memory: Some(Memory::gib(16)),
+            cpus: None,
+            restart: None,
+        },
+        memory_env: None,
+        // Checks that the game port (8211, `2013` in hex) is bound
+        health_check: Some(HealthCheck {
+            test: &["CMD-SHELL", "grep -qi ':2013 ' /proc/net/udp /proc/net/udp6"],
+            start_period: 600,
+        }),
+        query: Some(QueryProtocol::A2s(27015)),
+        // The admin password is also the RCON password
+        rcon: Some(RconConfiguration {
+            port: 25575,
+            envs: &["RCON_ENABLED=true", "ADMIN_PASSWORD={password}"],
+            password_file: None,
+        }),
+        command_delivery: Some(CommandDelivery::Rcon),
+        // Palworld can kick and ban players but has no whitelist or admins
+        players: None,
+        player_envs: None,
+        saves: Some("/palworld/Pal/Saved/SaveGames/0"),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_games_by_any_image_reference() {
+        for reference in [
+            "itzg/minecraft-server",
+            "itzg/minecraft-server:java17",
+            "docker.io/itzg/minecraft-server:latest",
+            "docker.io/itzg/minecraft-server@sha256:0123",
+        ] {
+            assert_eq!(
+                Game::find_by_image(reference).map(|game| game.name),
+                Some(GameName::Minecraft),
+                "{}",
+                reference
+            );
+        }
+        assert_eq!(image_repository("docker.io/library/nginx"), "nginx");
+        assert_eq!(
+            image_repository("localhost:5000/minecraft:1.17"),
+            "localhost:5000/minecraft"
+        );
+        assert!(Game::find_by_image("localhost:5000/itzg/minecraft-server").is_none());
+    }
+}
@@ -0,0 +1,78 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::Clap;
+use serde_json::json;
+
+const MOD_LIST: &str = "/factorio/mods/mod-list.json";
+const MAP_GEN_SETTINGS: &str = "/factorio/config/map-gen-settings.json";
+
+#[derive(Clap, Default)]
+pub struct FactorioOptions {
+    /// Mod to enable, can be repeated (Factorio only)
+    ///
+    /// Missing mods are downloaded if `FACTORIO_USERNAME` and `FACTORIO_TOKEN` are set.
+    #[clap(long = "mod", name = "factorio-mod")]
+    pub factorio_mods: Vec<String>,
+    /// Save to load, without `--generate-new-save` it has to exist (Factorio only)
+    #[clap(long)]
+    pub save: Option<String>,
+    /// Create a new map as `--save` (Factorio only)
+    #[clap(long, requires = "save")]
+    pub generate_new_save: bool,
+    /// File with the `map-gen-settings.json` for the new map (Factorio only)
+    #[clap(long, requires = "generate-new-save")]
+    pub map_gen_settings: Option<PathBuf>,
+}
+
+impl FactorioOptions {
+    pub fn is_empty(&self) -> bool {
+        self.factorio_mods.is_empty() && self.save.is_none()
+    }
+
+    /// The envs understood by factoriotools/factorio
+    pub fn envs(&self) -> Vec<String> {
+        let mut envs = Vec::new();
+        if let Some(save) = &self.save {
+            envs.push(format!("SAVE_NAME={}", save));
+            envs.push("LOAD_LATEST_SAVE=false".into());
+        }
+        if self.generate_new_save {
+            envs.push("GENERATE_NEW_SAVE=true".into());
+        }
+        if !self.factorio_mods.is_empty() {
+            if let (Ok(username), Ok(token)) =
+                (env::var("FACTORIO_USERNAME"), env::var("FACTORIO_TOKEN"))
+            {
+                envs.push("UPDATE_MODS_ON_START=true".into());
+                envs.push(format!("USERNAME={}", username));
+                envs.push(format!("TOKEN={}", token));
+            }
+        }
+        envs
+    }
+
+    /// Files to upload into the data volume before the server is started
+    pub fn files(&self) -> Result<Vec<(&'static str, String)>> {
+        let mut files = Vec::new();
+        if !self.factorio_mods.is_empty() {
+            // The base mod is disabled if it is missing from the list
+            let mods: Vec<_> = Some("base")
+                .into_iter()
+                .chain(self.factorio_mods.iter().map(String::as_str))
+                .map(|name| json!({ "name": name, "enabled": true }))
+                .collect();
+            files.push((MOD_LIST, json!({ "mods": mods }).to_string()));
+        }
+        if let Some(path) = &self.map_gen_settings {
+            let settings = fs::read_to_string(path)
+                .map_err(|e| anyhow!("Unable to read `{}`: {}", path.display(), e))?;
+            serde_json::from_str::<serde_json::Value>(&settings)
+                .map_err(|e| anyhow!("Invalid map gen settings `{}`: {}", path.display(), e))?;
+            files.push((MAP_GEN_SETTINGS, settings));
+        }
+        Ok(files)
+    }
+}
@@ -0,0 +1,154 @@
+use std::env;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Error, Result};
+use clap::{ArgEnum, Clap};
+
+/// Server software supported by itzg/minecraft-server
+#[derive(Clone, Copy, Debug, ArgEnum, PartialEq)]
+pub enum ServerType {
+    Vanilla,
+    Paper,
+    Purpur,
+    Spigot,
+    Bukkit,
+    Fabric,
+    Quilt,
+    Forge,
+}
+
+impl ServerType {
+    fn env(self) -> &'static str {
+        use ServerType::*;
+        match self {
+            Vanilla => "VANILLA",
+            Paper => "PAPER",
+            Purpur => "PURPUR",
+            Spigot => "SPIGOT",
+            Bukkit => "BUKKIT",
+            Fabric => "FABRIC",
+            Quilt => "QUILT",
+            Forge => "FORGE",
+        }
+    }
+
+    /// Whether additions are called plugins instead of mods
+    fn has_plugins(self) -> bool {
+        use ServerType::*;
+        matches!(self, Paper | Purpur | Spigot | Bukkit)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Modpack {
+    /// Slug or page URL
+    CurseForge(String),
+    /// Slug, id or page URL
+    Modrinth(String),
+}
+
+impl FromStr for Modpack {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(slug) = s.strip_prefix("curseforge:") {
+            Ok(Self::CurseForge(slug.into()))
+        } else if let Some(slug) = s.strip_prefix("modrinth:") {
+            Ok(Self::Modrinth(slug.into()))
+        } else if s.contains("curseforge.com/") {
+            Ok(Self::CurseForge(s.into()))
+        } else if s.contains("modrinth.com/") {
+            Ok(Self::Modrinth(s.into()))
+        } else {
+            Err(anyhow!(
+                "Unknown modpack `{}`, expected `curseforge:<slug>`, `modrinth:<slug>` or a page URL",
+                s
+            ))
+        }
+    }
+}
+
+#[derive(Clap, Default)]
+pub struct MinecraftOptions {
+    /// Server software to run (Minecraft only)
+    #[clap(long = "type", arg_enum)]
+    pub server_type: Option<ServerType>,
+    /// Modpack to install e.g. `modrinth:fabulously-optimized` (Minecraft only)
+    ///
+    /// CurseForge modpacks need an API key in `CF_API_KEY`.
+    #[clap(long, conflicts_with = "server-type")]
+    pub modpack: Option<Modpack>,
+    /// Mod or plugin as Modrinth slug or download URL, can be repeated (Minecraft only)
+    #[clap(long = "mods")]
+    pub mods: Vec<String>,
+}
+
+impl MinecraftOptions {
+    pub fn is_empty(&self) -> bool {
+        self.server_type.is_none() && self.modpack.is_none() && self.mods.is_empty()
+    }
+
+    /// The type shown in `ls`
+    pub fn server_type(&self) -> Option<String> {
+        match (&self.modpack, self.server_type) {
+            (Some(Modpack::CurseForge(_)), _) => Some("curseforge".into()),
+            (Some(Modpack::Modrinth(_)), _) => Some("modrinth".into()),
+            (None, Some(server_type)) => Some(server_type.env().to_lowercase()),
+            (None, None) => None,
+        }
+    }
+
+    /// The envs understood by itzg/minecraft-server
+    pub fn envs(&self) -> Result<Vec<String>> {
+        let mut envs = Vec::new();
+        match &self.modpack {
+            Some(Modpack::Modrinth(modpack)) => {
+                envs.push("TYPE=MODRINTH".into());
+                envs.push(format!("MODRINTH_MODPACK={}", modpack));
+            }
+            Some(Modpack::CurseForge(modpack)) => {
+                let api_key = env::var("CF_API_KEY").map_err(|_| {
+                    anyhow!("CurseForge modpacks need an API key in the environment variable `CF_API_KEY`")
+                })?;
+                envs.push("TYPE=AUTO_CURSEFORGE".into());
+                envs.push(format!("CF_API_KEY={}", api_key));
+                if modpack.contains("curseforge.com/") {
+                    envs.push(format!("CF_PAGE_URL={}", modpack));
+                } else {
+                    envs.push(format!("CF_SLUG={}", modpack));
+                }
+            }
+            None => {
+                if let Some(server_type) = self.server_type {
+                    envs.push(format!("TYPE={}", server_type.env()));
+                }
+            }
+        }
+
+        if !self.mods.is_empty() {
+            let plugins = match (&self.modpack, self.server_type) {
+                (Some(_), _) => false,
+                (None, Some(server_type)) if server_type != ServerType::Vanilla => {
+                    server_type.has_plugins()
+                }
+                _ => bail!("Vanilla servers cannot load mods, choose a different `--type`"),
+            };
+            let (urls, slugs): (Vec<_>, Vec<_>) = self
+                .mods
+                .iter()
+                .map(String::as_str)
+                .partition(|m| m.starts_with("https://") || m.starts_with("http://"));
+            if !urls.is_empty() {
+                envs.push(format!(
+                    "{}={}",
+                    if plugins { "PLUGINS" } else { "MODS" },
+                    urls.join(",")
+                ));
+            }
+            if !slugs.is_empty() {
+                envs.push(format!("MODRINTH_PROJECTS={}", slugs.join(",")));
+            }
+        }
+        Ok(envs)
+    }
+}
@@ -0,0 +1,4 @@
+//! Options only supported by a single game
+pub mod factorio;
+pub mod minecraft;
+pub mod valheim;
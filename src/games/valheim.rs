@@ -0,0 +1,130 @@
+use std::ops::Deref;
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+use clap::Clap;
+
+/// The name lloesche/valheim-server uses without `--server-name`
+const DEFAULT_SERVER_NAME: &str = "My Server";
+
+/// A password accepted by the Valheim server
+#[derive(Clone, Debug, PartialEq)]
+pub struct Password(String);
+
+impl FromStr for Password {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().count() < 5 {
+            bail!("Valheim passwords need at least 5 characters");
+        }
+        Ok(Self(s.into()))
+    }
+}
+
+impl Deref for Password {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Clap, Default)]
+pub struct ValheimOptions {
+    /// Name shown in the server browser (Valheim only)
+    #[clap(long)]
+    pub server_name: Option<String>,
+    /// World to load or create (Valheim only)
+    #[clap(long)]
+    pub world_name: Option<String>,
+    /// Password to join, at least 5 characters and not part of the server name (Valheim only)
+    #[clap(long)]
+    pub password: Option<Password>,
+    /// Hide the server from the server browser (Valheim only)
+    #[clap(long)]
+    pub private: bool,
+    /// Install the BepInEx mod loader (Valheim only)
+    ///
+    /// Mods are loaded from `/config/bepinex/plugins`.
+    #[clap(long)]
+    pub bepinex: bool,
+    /// Install ValheimPlus, which includes BepInEx (Valheim only)
+    #[clap(long, conflicts_with = "bepinex")]
+    pub valheim_plus: bool,
+}
+
+impl ValheimOptions {
+    pub fn is_empty(&self) -> bool {
+        self.server_name.is_none()
+            && self.world_name.is_none()
+            && self.password.is_none()
+            && !self.private
+            && !self.bepinex
+            && !self.valheim_plus
+    }
+
+    /// Checks the rules Valheim enforces only once the server starts
+    pub fn validate(&self) -> Result<()> {
+        let server_name = self.server_name.as_deref().unwrap_or(DEFAULT_SERVER_NAME);
+        if let Some(password) = &self.password {
+            if server_name.contains(&**password) || password.contains(server_name) {
+                bail!(
+                    "The Valheim password must not be part of the server name `{}` or contain it",
+                    server_name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The envs understood by lloesche/valheim-server
+    pub fn envs(&self) -> Vec<String> {
+        let mut envs = Vec::new();
+        if let Some(server_name) = &self.server_name {
+            envs.push(format!("SERVER_NAME={}", server_name));
+        }
+        if let Some(world_name) = &self.world_name {
+            envs.push(format!("WORLD_NAME={}", world_name));
+        }
+        if let Some(password) = &self.password {
+            envs.push(format!("SERVER_PASS={}", &**password));
+        }
+        if self.private {
+            envs.push("SERVER_PUBLIC=false".into());
+        }
+        if self.bepinex {
+            envs.push("BEPINEX=true".into());
+        }
+        if self.valheim_plus {
+            envs.push("VALHEIM_PLUS=true".into());
+        }
+        envs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(server_name: &str, password: &str) -> ValheimOptions {
+        ValheimOptions {
+            server_name: Some(server_name.into()),
+            password: Some(password.parse().unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn password_must_not_overlap_server_name() {
+        assert!(options("My Server Deluxe", "Server").validate().is_err());
+        assert!(options("Viking", "Viking123").validate().is_err());
+        assert!(options("Viking", "Odin123").validate().is_ok());
+        // The default name applies without `--server-name`
+        let options = ValheimOptions {
+            password: Some("Server".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(options.validate().is_err());
+    }
+}
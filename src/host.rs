@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Result};
+use bollard::{ClientVersion, Docker};
+use docker_game_servers::ServerManager;
+
+use crate::cli::Opt;
+use crate::config::{Config, HostConfig};
+use crate::TIME_OUT;
+
+pub(crate) const CLIENT_VERSION: ClientVersion = ClientVersion {
+    major_version: 1,
+    minor_version: 40,
+};
+
+/// Which engine a local host talks to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Runtime {
+    Docker,
+    PodmanUser,
+    PodmanSystem,
+}
+
+/// A container engine together with the name it is shown as
+pub struct Host {
+    pub name: String,
+    pub manager: ServerManager,
+    /// Seconds to wait for this host before giving up
+    pub time_out: u64,
+    /// Where the published ports of servers on this host can be reached
+    pub address: String,
+    /// Only known for the local engine
+    pub runtime: Option<Runtime>,
+}
+
+impl Host {
+    /// The engine selected on the command line, either a configured `--host`
+    /// or the local Docker/Podman
+    pub fn selected(opt: &Opt, config: &Config) -> Result<Self> {
+        if let Some(name) = &opt.host {
+            Self::connect(config.host(name)?)
+        } else {
+            Ok(Self::local(opt))
+        }
+    }
+
+    /// The local engine followed by every configured host
+    ///
+    /// Hosts that cannot be set up are reported as warnings and skipped.
+    pub fn all(opt: &Opt, config: &Config) -> Vec<Self> {
+        let mut hosts = vec![Self::local(opt)];
+        for host in &config.hosts {
+            match Self::connect(host) {
+                Ok(host) => hosts.push(host),
+                Err(e) => eprintln!("Warning: Skipping host `{}`: {}", host.name, e),
+            }
+        }
+        hosts
+    }
+
+    pub fn local(opt: &Opt) -> Self {
+        let runtime = match (opt.podman_system, opt.podman_user) {
+            (false, true) => Runtime::PodmanUser,
+            (true, false) => Runtime::PodmanSystem,
+            _ => Runtime::Docker,
+        };
+        let docker = match runtime {
+            Runtime::PodmanUser => Docker::connect_with_socket(
+                {
+                    let mut rt_dir = dirs::runtime_dir()
+                        .expect("There should be a runtime dir ($XDG_RUNTIME_DIR)");
+                    rt_dir.push("podman/podman.sock");
+                    rt_dir
+                }
+                .to_str()
+                .expect("The runtime dir ($XDG_RUNTIME_DIR) is a valid str"),
+                TIME_OUT,
+                &CLIENT_VERSION,
+            ),
+            Runtime::PodmanSystem => Docker::connect_with_socket(
+                "/var/run/podman/podman.sock",
+                TIME_OUT,
+                &CLIENT_VERSION,
+            ),
+            Runtime::Docker => Docker::connect_with_local_defaults(),
+        }
+        .expect("Setup Docker connection (cannot error currently)");
+        Self {
+            name: "local".into(),
+            manager: ServerManager::new(docker),
+            time_out: TIME_OUT,
+            address: "localhost".into(),
+            runtime: Some(runtime),
+        }
+    }
+
+    pub fn connect(
+        HostConfig {
+            name,
+            uri,
+            time_out,
+        }: &HostConfig,
+    ) -> Result<Self> {
+        let (docker, address) = if let Some(path) = uri.strip_prefix("unix://") {
+            (
+                Docker::connect_with_socket(path, *time_out, &CLIENT_VERSION)?,
+                "localhost".into(),
+            )
+        } else if let Some(authority) = uri
+            .strip_prefix("tcp://")
+            .or_else(|| uri.strip_prefix("http://"))
+        {
+            (
+                Docker::connect_with_http(uri, *time_out, &CLIENT_VERSION)?,
+                authority
+                    .split(|c| c == ':' || c == '/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        } else {
+            return Err(anyhow!(
+                "Unsupported uri `{}`, expected `unix://` or `tcp://`",
+                uri
+            ));
+        };
+        Ok(Self {
+            name: name.clone(),
+            manager: ServerManager::new(docker),
+            time_out: *time_out,
+            address,
+            runtime: None,
+        })
+    }
+}
@@ -0,0 +1,26 @@
+//! Run and manage game servers in Docker or Podman containers
+//!
+//! [`ServerManager`] wraps the connection to an engine and returns plain data,
+//! the `dgs` binary is built on top of it.
+#![feature(iter_intersperse, never_type, in_band_lifetimes)]
+
+pub mod archive;
+pub mod engine;
+mod error;
+mod filter;
+mod game;
+pub mod games;
+mod manager;
+pub mod manifest;
+mod options;
+pub mod query;
+pub mod rcon;
+pub mod resources;
+mod server_info;
+
+pub use error::Error;
+pub use filter::{LowerCaseString, ServerFilter};
+pub use game::*;
+pub use manager::{CreatedServer, PullProgress, ServerManager, ServerSetup};
+pub use options::GameOptions;
+pub use server_info::{BasicServerInfo, InvalidServer, Port};
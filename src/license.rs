@@ -0,0 +1,69 @@
+//! Remembers which game licenses the user accepted
+use std::fs::{create_dir_all, read_to_string, OpenOptions};
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use docker_game_servers::{Game, License};
+
+use crate::config::Config;
+
+/// One game name per line
+fn path() -> PathBuf {
+    Config::dir().join("accepted-licenses")
+}
+
+fn is_accepted(game: &Game) -> Result<bool> {
+    let path = path();
+    if !path.exists() {
+        return Ok(false);
+    }
+    Ok(read_to_string(&path)
+        .with_context(|| format!("Unable to read `{}`", path.display()))?
+        .lines()
+        .any(|line| line.trim() == &*game.name))
+}
+
+fn remember(game: &Game) -> Result<()> {
+    let path = path();
+    create_dir_all(Config::dir())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Unable to write `{}`", path.display()))?;
+    writeln!(file, "{}", game.name)?;
+    Ok(())
+}
+
+/// Makes sure the user accepted the license of `game` before running it
+///
+/// The user is asked on first use unless `accept` is set, the answer is remembered.
+pub fn ensure_accepted(game: &Game, accept: bool) -> Result<()> {
+    let License { name, url, .. } = match &game.license {
+        Some(license) => license,
+        None => return Ok(()),
+    };
+    if is_accepted(game)? {
+        return Ok(());
+    }
+    if !accept {
+        println!("Running {} requires accepting the {}:", game.name, name);
+        println!("{}", url);
+        if !termion::is_tty(&stdin()) {
+            bail!(
+                "Accept the {} with `--accept-eula` to run {}",
+                name,
+                game.name
+            );
+        }
+        print!("Do you accept it? [y/N] ");
+        stdout().flush()?;
+        let mut answer = String::new();
+        stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            bail!("The {} was not accepted", name);
+        }
+    }
+    remember(game)
+}
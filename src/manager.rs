@@ -0,0 +1,1348 @@
+//! Creating and managing servers on a single engine
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::future::Future;
+use std::iter;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use bollard::container::{Config, NetworkingConfig};
+use bollard::image::CreateImageOptions;
+use bollard::models::{
+    ContainerConfig, ContainerInspectResponse, ContainerStateStatusEnum, EndpointSettings,
+    HealthConfig, HealthStatusEnum, HostConfig, MountPoint, PortBinding, ProgressDetail,
+    SystemEventsResponse, Volume,
+};
+use bollard::Docker;
+use chrono::prelude::*;
+use futures_util::{Stream, TryStreamExt};
+use portpicker::pick_unused_port;
+use tokio::time::{sleep, timeout};
+
+use crate::archive::Archive;
+use crate::engine::{Engine, EngineVersion, Stdin};
+use crate::manifest::{Change, Manifest, Plan, MANIFEST_LABEL};
+use crate::resources::{Limits, Usage};
+use crate::{
+    image_repository, BasicServerInfo, CommandDelivery, Error, Game, GameOptions, HealthCheck,
+    InvalidServer, LowerCaseString, PlayerEnvs, PlayerManagement, Port, PortConfiguration,
+    ServerFilter, VersionConfiguration, GAMES, RCON_PASSWORD_LABEL, SERVER_TYPE_LABEL,
+};
+
+/// A server that was created but not started yet
+#[derive(Debug)]
+pub struct CreatedServer {
+    pub id: String,
+    /// The ports the server will be published on once it is started
+    pub ports: Vec<Port>,
+}
+
+/// Settings for servers meant to be kept, temporary servers use the defaults
+#[derive(Clone, Debug, Default)]
+pub struct ServerSetup {
+    /// Name of the container, defaults to `dgs-tmp_<game>_<time>`
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    /// Host ports in the order of the game's ports, TCP and UDP of the same port
+    /// count once
+    ///
+    /// Free ports are picked for the rest.
+    pub ports: Vec<u16>,
+    /// Set after the envs of the game and its options, so they take precedence
+    /// e.g. `DIFFICULTY=hard`
+    pub envs: Vec<String>,
+    /// Volumes in the `docker run -v` syntax e.g. `survival:/data`
+    pub volumes: Vec<String>,
+    pub labels: HashMap<String, String>,
+}
+
+/// A status update while pulling an image
+#[derive(Debug)]
+pub struct PullProgress {
+    pub status: String,
+    pub current: Option<i64>,
+    pub total: Option<i64>,
+}
+
+impl Display for PullProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.current, self.total) {
+            (Some(current), Some(total)) => write!(f, "{}: ({}/{})", self.status, current, total),
+            _ => write!(f, "{}", self.status),
+        }
+    }
+}
+
+/// Manages the dgs servers of a single container engine
+///
+/// Nothing is printed, every method returns its results instead.
+#[derive(Clone)]
+pub struct ServerManager<E: Engine = Docker> {
+    engine: E,
+}
+
+impl<E: Engine> ServerManager<E> {
+    pub fn new(engine: E) -> Self {
+        Self { engine }
+    }
+
+    pub fn engine(&self) -> &E {
+        &self.engine
+    }
+
+    /// Returns all dgs servers matching the `filter`
+    ///
+    /// Containers labelled as dgs servers that are not compatible are skipped,
+    /// [`ServerManager::list_checked`] returns them as well.
+    pub async fn list(&self, filter: &ServerFilter) -> Result<Vec<BasicServerInfo>> {
+        Ok(self.list_checked(filter).await?.0)
+    }
+
+    /// Returns all dgs servers matching the `filter` and the containers
+    /// labelled as dgs servers that are not compatible
+    pub async fn list_checked(
+        &self,
+        ServerFilter {
+            name,
+            game,
+            tags,
+            state: status,
+        }: &ServerFilter,
+    ) -> Result<(Vec<BasicServerInfo>, Vec<InvalidServer>)> {
+        let mut filters = HashMap::new();
+        filters.insert(
+            "label".to_owned(),
+            tags.iter()
+                .map(|tag| "dgs-".to_owned() + tag)
+                // The default Tag every server has
+                .chain(iter::once("dgs".into()))
+                .collect(),
+        );
+        if let Some(game_name) = *game {
+            let game = GAMES.iter().find(|game| game.name == game_name);
+            let game = game.ok_or_else(|| {
+                let games: Vec<_> = GAMES
+                    .iter()
+                    .filter(|game| game.name.contains(&*game_name))
+                    .collect();
+                match games.len() {
+                    1 => Ok(games[0]),
+                    0 => bail!("Unable to find a matching game for: `{}`", &*game_name),
+
+                    _ => bail!(
+                        "Unable to find unique matching game for: `{}`, found: {}",
+                        &*game_name,
+                        games
+                            .iter()
+                            .map(|game| "`".to_owned() + &game.name + "`")
+                            .intersperse(", ".to_owned())
+                            .collect::<String>()
+                    ),
+                }
+            });
+            if let Ok(game) = game {
+                filters.insert("ancestor".into(), vec![game.image.into()]);
+            }
+        };
+        if let Some(status) = status {
+            filters.insert("status".into(), vec![status.to_string().to_lowercase()]);
+        }
+        let search_name = name.as_ref().map(|s| s.to_lowercase()).unwrap_or_default();
+        let servers = self.engine.list(filters).await?;
+        let mut valid = Vec::new();
+        let mut invalid = Vec::new();
+        for server in servers {
+            match BasicServerInfo::try_from(server.clone()) {
+                Ok(server) if server.name.to_lowercase().contains(&search_name) => {
+                    valid.push(server)
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    let server = InvalidServer::new(&server, &error);
+                    if server.name().to_lowercase().contains(&search_name) {
+                        invalid.push(server);
+                    }
+                }
+            }
+        }
+        Ok((valid, invalid))
+    }
+
+    /// Finds the server called `name` (case is ignored)
+    ///
+    /// If no server has exactly this name, a unique partial match is used instead.
+    pub async fn find(&self, name: &str) -> Result<BasicServerInfo> {
+        let mut servers = self
+            .list(&ServerFilter {
+                name: Some(name.into()),
+                ..Default::default()
+            })
+            .await?;
+        let name = name.to_lowercase();
+        if let Some(index) = servers
+            .iter()
+            .position(|server| server.name.trim_start_matches('/').to_lowercase() == name)
+        {
+            return Ok(servers.swap_remove(index));
+        }
+        match servers.len() {
+            1 => Ok(servers.remove(0)),
+            0 => Err(Error::NotFound(name).into()),
+            _ => Err(Error::AmbiguousName {
+                name,
+                matches: servers.into_iter().map(|server| server.name).collect(),
+            }
+            .into()),
+        }
+    }
+
+    /// Pulls the image of `game`, `version` is only used for games versioned by tag
+    pub fn pull(
+        &self,
+        game: &Game,
+        version: Option<&str>,
+    ) -> impl Stream<Item = Result<PullProgress>> + '_ {
+        let mut options = CreateImageOptions {
+            from_image: game.image.to_string(),
+            repo: "docker.io".into(),
+            ..Default::default()
+        };
+        if let (VersionConfiguration::Tag, Some(version)) = (&game.version.config, version) {
+            options.tag = version.into();
+        }
+        let image = game.image;
+        self.engine
+            .pull(options)
+            .map_err(move |error| pull_failed(image, error.to_string()))
+            .and_then(move |progress| async move {
+                if let Some(error) = progress.error {
+                    return Err(pull_failed(image, error));
+                }
+                let (current, total) = match progress.progress_detail {
+                    Some(ProgressDetail { current, total }) => (current, total),
+                    None => (None, None),
+                };
+                Ok(PullProgress {
+                    status: progress
+                        .status
+                        .unwrap_or_else(|| "Downloading image".into()),
+                    current,
+                    total,
+                })
+            })
+    }
+
+    /// Creates a server for `game`, the image has to be pulled already
+    ///
+    /// Games with a license need `options.accept_eula`.
+    pub async fn create(&self, game: &'static Game, options: GameOptions) -> Result<CreatedServer> {
+        self.create_with(game, options, ServerSetup::default())
+            .await
+    }
+
+    /// Creates a server for `game` with additional settings from `setup`
+    pub async fn create_with(
+        &self,
+        game: &'static Game,
+        options: GameOptions,
+        setup: ServerSetup,
+    ) -> Result<CreatedServer> {
+        options.validate(game)?;
+        if let (Some(license), false) = (&game.license, options.accept_eula) {
+            bail!(
+                "{} can only be run after accepting the {}",
+                game.name,
+                license.name
+            );
+        }
+        let mut pb: HashMap<String, Option<Vec<PortBinding>>> = HashMap::new();
+        // TCP and UDP of the same container port share the port on the host
+        let mut host_ports: HashMap<u16, u16> = HashMap::new();
+        let mut ports = Vec::new();
+        for &(port, protocol) in game.ports.ports() {
+            let requested = setup.ports.get(host_ports.len()).copied();
+            let host_port = match (host_ports.get(&port), &game.ports, requested) {
+                (Some(host_port), _, _) => *host_port,
+                (None, PortConfiguration::Configurable(_), Some(requested)) => requested,
+                (None, PortConfiguration::Configurable(_), None) => {
+                    pick_port(&host_ports.values().copied().collect::<Vec<_>>())?
+                }
+                (None, PortConfiguration::NonConfigurable(_), Some(requested))
+                    if requested != port =>
+                {
+                    return Err(Error::unsupported(
+                        game.name,
+                        format!("publishing port {} on another port", port),
+                    )
+                    .into())
+                }
+                (None, PortConfiguration::NonConfigurable(_), _) => port,
+            };
+            host_ports.insert(port, host_port);
+            ports.push(Port {
+                public: host_port,
+                private: port,
+                typ: protocol,
+            });
+            pb.insert(
+                format!("{}/{}", port, protocol),
+                Some(vec![PortBinding {
+                    host_ip: None,
+                    host_port: Some(host_port.to_string()),
+                }]),
+            );
+        }
+        let limits = options.limits.or(game.limits);
+        let mut envs: Vec<_> = game
+            .envs
+            .iter()
+            .chain(game.license.as_ref().map(|license| &license.env))
+            .map(|env| env.to_string())
+            .collect();
+        if let (VersionConfiguration::Env(name), Some(version)) =
+            (game.version.config.clone(), options.version)
+        {
+            envs.push(format!("{}={}", name, version));
+        }
+        if let (Some(name), Some(memory)) = (game.memory_env, limits.memory) {
+            envs.push(format!("{}={}", name, memory));
+        }
+        let mut labels = setup.labels;
+        labels.insert("dgs".to_string(), "dgs".to_string());
+        for tag in &setup.tags {
+            labels.insert(format!("dgs-{}", tag.to_lowercase()), String::new());
+        }
+        envs.extend(options.minecraft.envs()?);
+        envs.extend(options.factorio.envs());
+        envs.extend(options.valheim.envs());
+        envs.extend(setup.envs);
+        if let Some(server_type) = options.minecraft.server_type() {
+            labels.insert(SERVER_TYPE_LABEL.to_string(), server_type);
+        }
+        // Files uploaded into the container before it is started
+        let mut files = options.factorio.files()?;
+        if let Some(rcon) = &game.rcon {
+            let password = random_password();
+            let host_port = pick_port(&host_ports.values().copied().collect::<Vec<_>>())?;
+            // RCON grants full control over the server, so it is only reachable from the host
+            pb.insert(
+                format!("{}/tcp", rcon.port),
+                Some(vec![PortBinding {
+                    host_ip: Some("127.0.0.1".into()),
+                    host_port: Some(host_port.to_string()),
+                }]),
+            );
+            envs.extend(
+                rcon.envs
+                    .iter()
+                    .map(|env| env.replace("{password}", &password)),
+            );
+            if let Some(password_file) = rcon.password_file {
+                files.push((password_file, password.clone()));
+            }
+            labels.insert(RCON_PASSWORD_LABEL.to_string(), password);
+        }
+        if !options.whitelist.is_empty() || !options.ops.is_empty() {
+            match (&game.player_envs, &game.players) {
+                (Some(PlayerEnvs { whitelist, ops }), _) => {
+                    for (name, players) in [(whitelist, &options.whitelist), (ops, &options.ops)] {
+                        if !players.is_empty() {
+                            envs.push(format!("{}={}", name, players.join(",")));
+                        }
+                    }
+                }
+                (None, Some(PlayerManagement::Files { whitelist, ops, .. })) => {
+                    for (path, players) in [(whitelist, &options.whitelist), (ops, &options.ops)] {
+                        if !players.is_empty() {
+                            files.push((*path, players.join("\n") + "\n"));
+                        }
+                    }
+                }
+                (None, Some(PlayerManagement::Commands { .. })) => bail!(
+                    "{} does not support setting players on creation, use `dgs server players` instead",
+                    game.name
+                ),
+                (None, None) => return Err(Error::unsupported(game.name, "managing players").into()),
+            }
+        }
+        let config = Config {
+            image: Some(game.image.to_string()),
+            env: Some(envs),
+            exposed_ports: Some(
+                pb.keys()
+                    .map(|port| (port.clone(), HashMap::new()))
+                    .collect(),
+            ),
+            healthcheck: game.health_check.as_ref().map(HealthCheck::config),
+            open_stdin: Some(matches!(
+                game.command_delivery,
+                Some(CommandDelivery::Stdin)
+            )),
+            host_config: Some(HostConfig {
+                port_bindings: Some(pb),
+                binds: Some(setup.volumes).filter(|volumes| !volumes.is_empty()),
+                ..limits.host_config()
+            }),
+            labels: Some(labels),
+            ..Default::default()
+        };
+
+        let name = setup.name.unwrap_or_else(|| {
+            format!(
+                "dgs-tmp_{}_{}",
+                game.name,
+                Local::now().format("%Y-%m-%d_%H-%M-%S%.3f")
+            )
+        });
+        let id = self.engine.create(&name, config).await?;
+
+        if !files.is_empty() {
+            let archive = files
+                .into_iter()
+                .try_fold(Archive::default(), |archive, (path, content)| {
+                    archive.file(path, content.as_bytes())
+                })?;
+            self.upload(&id, archive).await?;
+        }
+        Ok(CreatedServer { id, ports })
+    }
+
+    /// Brings an existing container of a supported game under dgs management
+    ///
+    /// Labels cannot be changed, so the container is recreated with the same
+    /// configuration and volumes, optionally as `name` and with `tags`.
+    pub async fn adopt(
+        &self,
+        container: &str,
+        name: Option<&str>,
+        tags: &[LowerCaseString],
+    ) -> Result<BasicServerInfo> {
+        let inspect = self.engine.inspect(container).await?;
+        let id = inspect.id.unwrap_or_default();
+        let old_name = inspect
+            .name
+            .unwrap_or_default()
+            .trim_start_matches('/')
+            .to_string();
+        let config = inspect.config.unwrap_or_default();
+        let image = config.image.clone().unwrap_or_default();
+        if Game::find_by_image(&image).is_none() {
+            bail!(
+                "`{}` runs `{}`, which is not the image of a game supported by dgs",
+                old_name,
+                image
+            );
+        }
+        let mut labels = config.labels.unwrap_or_default();
+        if labels.contains_key("dgs") {
+            bail!("`{}` is already managed by dgs", old_name);
+        }
+        labels.insert("dgs".to_string(), "dgs".to_string());
+        for tag in tags {
+            labels.insert("dgs-".to_owned() + tag, String::new());
+        }
+
+        let mut host_config = inspect.host_config.unwrap_or_default();
+        let volumes = unnamed_volumes(&host_config, inspect.mounts, &[]);
+        host_config
+            .binds
+            .get_or_insert_with(Vec::new)
+            .extend(volumes);
+
+        let networks = inspect
+            .network_settings
+            .and_then(|settings| settings.networks)
+            .unwrap_or_default();
+        if networks.len() > 1 {
+            bail!(
+                "`{}` is connected to {} networks, only containers in a single network can be adopted",
+                old_name,
+                networks.len()
+            );
+        }
+        let networking_config = networks.into_iter().next().map(|(network, endpoint)| {
+            let mut endpoints_config = HashMap::new();
+            endpoints_config.insert(
+                network,
+                EndpointSettings {
+                    // The short id of the old container is an alias as well
+                    aliases: endpoint.aliases.map(|aliases| {
+                        aliases
+                            .into_iter()
+                            .filter(|alias| !id.starts_with(alias.as_str()))
+                            .collect()
+                    }),
+                    links: endpoint.links,
+                    ipam_config: endpoint.ipam_config,
+                    ..Default::default()
+                },
+            );
+            NetworkingConfig { endpoints_config }
+        });
+
+        let new_config = Config {
+            // Generated hostnames are the short id of the old container
+            hostname: config
+                .hostname
+                .filter(|hostname| !id.starts_with(hostname.as_str())),
+            domainname: config.domainname,
+            user: config.user,
+            attach_stdin: config.attach_stdin,
+            attach_stdout: config.attach_stdout,
+            attach_stderr: config.attach_stderr,
+            exposed_ports: config.exposed_ports,
+            tty: config.tty,
+            open_stdin: config.open_stdin,
+            stdin_once: config.stdin_once,
+            env: config.env,
+            cmd: config.cmd,
+            healthcheck: config.healthcheck,
+            args_escaped: config.args_escaped,
+            image: config.image,
+            volumes: config.volumes,
+            working_dir: config.working_dir,
+            entrypoint: config.entrypoint,
+            network_disabled: config.network_disabled,
+            on_build: config.on_build,
+            labels: Some(labels),
+            stop_signal: config.stop_signal,
+            stop_timeout: config.stop_timeout,
+            shell: config.shell,
+            host_config: Some(host_config),
+            networking_config,
+            ..Default::default()
+        };
+
+        let running =
+            inspect.state.and_then(|state| state.status) == Some(ContainerStateStatusEnum::RUNNING);
+        let new_id = self
+            .replace(&id, &old_name, "before-adopt", running, || {
+                self.engine.create(name.unwrap_or(&old_name), new_config)
+            })
+            .await?;
+        if running {
+            self.start(&new_id).await?;
+        }
+        self.list(&ServerFilter::default())
+            .await?
+            .into_iter()
+            .find(|server| server.id == new_id)
+            .ok_or_else(|| {
+                anyhow!(
+                    "The adopted container `{}` is not compatible with dgs",
+                    new_id
+                )
+            })
+    }
+
+    /// The changes needed for the servers on the engine to match `manifest`
+    ///
+    /// Servers are matched by name. With `prune`, servers created from a manifest
+    /// that are no longer listed in it are removed.
+    pub async fn plan(&self, manifest: &Manifest, prune: bool) -> Result<Plan> {
+        let mut servers = self.list(&ServerFilter::default()).await?;
+        let mut changes = Vec::new();
+        for entry in &manifest.servers {
+            let server = match servers.iter().position(|server| {
+                server
+                    .name
+                    .trim_start_matches('/')
+                    .eq_ignore_ascii_case(&entry.name)
+            }) {
+                Some(index) => servers.remove(index),
+                None => {
+                    changes.push(Change::Create(entry.clone()));
+                    continue;
+                }
+            };
+            let changed = entry.changed(self.manifest_label(&server.id).await?.as_deref());
+            changes.push(if changed.is_empty() {
+                Change::Unchanged(server)
+            } else {
+                Change::Recreate {
+                    server,
+                    manifest: entry.clone(),
+                    changed,
+                }
+            });
+        }
+        if prune {
+            for server in servers {
+                if self.manifest_label(&server.id).await?.is_some() {
+                    changes.push(Change::Remove(server));
+                }
+            }
+        }
+        Ok(Plan { changes })
+    }
+
+    /// The manifest entry the server was created from
+    async fn manifest_label(&self, container_id: &str) -> Result<Option<String>> {
+        Ok(self.labels(container_id).await?.remove(MANIFEST_LABEL))
+    }
+
+    async fn labels(&self, container_id: &str) -> Result<HashMap<String, String>> {
+        Ok(self
+            .engine
+            .inspect(container_id)
+            .await?
+            .config
+            .and_then(|config| config.labels)
+            .unwrap_or_default())
+    }
+
+    /// Carries out a single change of a [`Plan`], the images have to be pulled already
+    ///
+    /// Recreated servers of the same game keep their anonymous volumes, the old
+    /// server is restored if the new one cannot be created.
+    pub async fn apply(&self, change: &Change) -> Result<()> {
+        match change {
+            Change::Create(manifest) => {
+                let (game, options, setup) = manifest.setup()?;
+                let server = self.create_with(game, options, setup).await?;
+                self.start(&server.id).await
+            }
+            Change::Recreate {
+                server, manifest, ..
+            } => {
+                let (game, options, mut setup) = manifest.setup()?;
+                if server.game.name == game.name {
+                    let inspect = self.engine.inspect(&server.id).await?;
+                    let volumes = unnamed_volumes(
+                        &inspect.host_config.unwrap_or_default(),
+                        inspect.mounts,
+                        &setup.volumes,
+                    );
+                    setup.volumes.extend(volumes);
+                    // Ports not pinned by the manifest keep their port on the host
+                    let mut container_ports = Vec::new();
+                    for &(port, _) in game.ports.ports() {
+                        if !container_ports.contains(&port) {
+                            container_ports.push(port);
+                        }
+                    }
+                    for port in container_ports.into_iter().skip(setup.ports.len()) {
+                        match server.ports.iter().find(|old| old.private == port) {
+                            Some(old) => setup.ports.push(old.public),
+                            None => break,
+                        }
+                    }
+                }
+                let new_id = self
+                    .replace(
+                        &server.id,
+                        server.name.trim_start_matches('/'),
+                        "before-apply",
+                        server.status == ContainerStateStatusEnum::RUNNING,
+                        || async move { Ok(self.create_with(game, options, setup).await?.id) },
+                    )
+                    .await?;
+                self.start(&new_id).await
+            }
+            Change::Remove(server) => {
+                if server.status == ContainerStateStatusEnum::RUNNING {
+                    self.stop(&server.id).await?;
+                }
+                self.remove(&server.id).await
+            }
+            Change::Unchanged(_) => Ok(()),
+        }
+    }
+
+    /// Replaces the container `id` with the one `create` returns the id of
+    ///
+    /// The old container is stopped and renamed to `<name>_<suffix>` to free its
+    /// name, and only removed once the new one is created. If creating fails, it
+    /// gets its name back and is started again if it was `running`.
+    async fn replace<F, C>(
+        &self,
+        id: &str,
+        name: &str,
+        suffix: &str,
+        running: bool,
+        create: C,
+    ) -> Result<String>
+    where
+        C: FnOnce() -> F,
+        F: Future<Output = Result<String>>,
+    {
+        if running {
+            self.stop(id).await?;
+        }
+        self.engine
+            .rename(id, &format!("{}_{}", name, suffix))
+            .await?;
+        match create().await {
+            Ok(new_id) => {
+                self.remove(id).await?;
+                Ok(new_id)
+            }
+            Err(error) => {
+                // Failing to restore the old container is less relevant than why
+                // the new one could not be created
+                self.engine.rename(id, name).await.ok();
+                if running {
+                    self.start(id).await.ok();
+                }
+                Err(error)
+            }
+        }
+    }
+
+    pub async fn start(&self, container_id: &str) -> Result<()> {
+        self.engine.start(container_id).await.map_err(|error| {
+            let message = error.to_string();
+            // Ports of non configurable games can be taken by another server
+            if message.contains("port is already allocated")
+                || message.contains("address already in use")
+            {
+                Error::PortUnavailable(message).into()
+            } else {
+                error
+            }
+        })
+    }
+
+    pub async fn stop(&self, container_id: &str) -> Result<()> {
+        self.engine.stop(container_id).await
+    }
+
+    pub async fn remove(&self, container_id: &str) -> Result<()> {
+        self.engine.remove(container_id).await
+    }
+
+    /// Waits until the engine reports the server as healthy
+    ///
+    /// Returns immediately for servers without a health check. Without
+    /// `time_out` this waits as long as the engine could take to report the
+    /// server as unhealthy, the start period plus all retries.
+    pub async fn wait_healthy(&self, container_id: &str, time_out: Option<Duration>) -> Result<()> {
+        let time_out = match time_out {
+            Some(time_out) => Some(time_out),
+            None => self
+                .engine
+                .inspect(container_id)
+                .await?
+                .config
+                .and_then(|config| config.healthcheck)
+                .map(|health_check| health_check_duration(&health_check)),
+        };
+        let healthy = async {
+            loop {
+                let state = self
+                    .engine
+                    .inspect(container_id)
+                    .await?
+                    .state
+                    .unwrap_or_default();
+                if state.status != Some(ContainerStateStatusEnum::RUNNING) {
+                    bail!("The server stopped while starting");
+                }
+                match state.health.and_then(|health| health.status) {
+                    Some(HealthStatusEnum::STARTING) => {}
+                    Some(HealthStatusEnum::UNHEALTHY) => bail!("The server is unhealthy"),
+                    _ => return Ok(()),
+                }
+                sleep(Duration::from_secs(1)).await;
+            }
+        };
+        match time_out {
+            Some(time_out) => timeout(time_out, healthy)
+                .await
+                .unwrap_or_else(|_| Err(Error::NotReady(time_out).into())),
+            None => healthy.await,
+        }
+    }
+
+    /// Starts the server and waits until it is healthy, see [`Self::wait_healthy`]
+    ///
+    /// A server that does not start or become healthy is stopped and removed again.
+    pub async fn start_healthy(
+        &self,
+        container_id: &str,
+        time_out: Option<Duration>,
+    ) -> Result<()> {
+        let started = match self.start(container_id).await {
+            Ok(()) => self.wait_healthy(container_id, time_out).await,
+            error => error,
+        };
+        if let Err(error) = started {
+            self.stop(container_id).await.ok();
+            self.remove(container_id).await.ok();
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Runs `cmd` in the container and returns its output
+    pub async fn exec(&self, container_id: &str, cmd: Vec<String>) -> Result<String> {
+        self.engine.exec(container_id, cmd).await
+    }
+
+    /// Extracts the `archive` into the root of the container
+    pub async fn upload(&self, container_id: &str, archive: Archive) -> Result<()> {
+        self.engine.upload(container_id, archive.finish()).await
+    }
+
+    /// Everything the engine knows about the container
+    pub async fn inspect(&self, container_id: &str) -> Result<ContainerInspectResponse> {
+        self.engine.inspect(container_id).await
+    }
+
+    /// The limits currently configured on a container
+    pub async fn limits(&self, container_id: &str) -> Result<Limits> {
+        Ok(Limits::from_host_config(
+            self.engine
+                .inspect(container_id)
+                .await?
+                .host_config
+                .unwrap_or_default(),
+        ))
+    }
+
+    /// The current resource usage of a running server
+    pub async fn stats(&self, container_id: &str) -> Result<Usage> {
+        self.engine.stats(container_id).await
+    }
+
+    /// The password dgs generated for RCON, `None` for servers created without RCON
+    pub async fn rcon_password(&self, container_id: &str) -> Result<Option<String>> {
+        Ok(self.labels(container_id).await?.remove(RCON_PASSWORD_LABEL))
+    }
+
+    /// The stdin of a server reading console commands from it
+    pub async fn attach_stdin(&self, container_id: &str) -> Result<Stdin> {
+        self.engine.attach_stdin(container_id).await
+    }
+
+    /// The config of the image the container was created from, which the
+    /// container inherits unless it overrides it
+    pub async fn image_config(&self, container_id: &str) -> Result<ContainerConfig> {
+        let image = self.engine.inspect(container_id).await?.image;
+        Ok(self
+            .engine
+            .inspect_image(&image.unwrap_or_default())
+            .await?
+            .config
+            .unwrap_or_default())
+    }
+
+    /// The image of the container referenced by its digest e.g.
+    /// `itzg/minecraft-server@sha256:…`
+    pub async fn image_digest(&self, container_id: &str) -> Result<String> {
+        let inspect = self.engine.inspect(container_id).await?;
+        let reference = inspect
+            .config
+            .and_then(|config| config.image)
+            .unwrap_or_default();
+        let repository = image_repository(&reference);
+        self.engine
+            .inspect_image(&inspect.image.unwrap_or_default())
+            .await?
+            .repo_digests
+            .into_iter()
+            .flatten()
+            .find(|digest| image_repository(digest) == repository)
+            .ok_or_else(|| {
+                anyhow!(
+                    "There is no digest for `{}`, images are only assigned one when pulled or pushed",
+                    reference
+                )
+            })
+    }
+
+    /// References of every image that is pulled e.g. `itzg/minecraft-server:latest`
+    pub async fn pulled_images(&self) -> Result<Vec<String>> {
+        Ok(self
+            .engine
+            .images()
+            .await?
+            .into_iter()
+            .flat_map(|image| image.repo_tags)
+            .collect())
+    }
+
+    /// Volumes not used by any container
+    pub async fn dangling_volumes(&self) -> Result<Vec<Volume>> {
+        let mut filters = HashMap::new();
+        filters.insert("dangling".to_owned(), vec!["true".to_owned()]);
+        self.engine.volumes(filters).await
+    }
+
+    /// Fails with [`Error::EngineUnreachable`] if the engine does not answer
+    pub async fn ping(&self) -> Result<()> {
+        self.engine
+            .ping()
+            .await
+            .map_err(|error| Error::EngineUnreachable(error.to_string()).into())
+    }
+
+    pub async fn engine_version(&self) -> Result<EngineVersion> {
+        self.engine.version().await
+    }
+
+    /// Events for changes to the state of dgs servers
+    pub fn events(&self) -> impl Stream<Item = Result<SystemEventsResponse>> + '_ {
+        let mut filters = HashMap::new();
+        filters.insert("type".to_owned(), vec!["container".to_owned()]);
+        filters.insert("label".to_owned(), vec!["dgs".to_owned()]);
+        filters.insert(
+            "event".to_owned(),
+            ["create", "start", "die", "destroy", "health_status"]
+                .iter()
+                .map(|event| event.to_string())
+                .collect(),
+        );
+        self.engine.events(filters)
+    }
+}
+
+/// Binds for the volumes of a container that are not mounted or bound already,
+/// neither in its `host_config` nor in `binds`
+///
+/// Recreating a container would replace these anonymous volumes with empty ones,
+/// so they are bound by name instead.
+fn unnamed_volumes(
+    host_config: &HostConfig,
+    mounts: Option<Vec<MountPoint>>,
+    binds: &[String],
+) -> Vec<String> {
+    let taken: Vec<&str> = host_config
+        .mounts
+        .iter()
+        .flatten()
+        .filter_map(|mount| mount.target.as_deref())
+        .chain(
+            host_config
+                .binds
+                .iter()
+                .flatten()
+                .chain(binds)
+                .filter_map(|bind| bind.split(':').nth(1)),
+        )
+        .collect();
+    mounts
+        .into_iter()
+        .flatten()
+        .filter_map(
+            |mount| match (mount.typ.as_deref(), mount.name, mount.destination) {
+                (Some("volume"), Some(volume), Some(destination))
+                    if !taken.contains(&destination.as_str()) =>
+                {
+                    let read_only = if mount.rw == Some(false) { ":ro" } else { "" };
+                    Some(format!("{}:{}{}", volume, destination, read_only))
+                }
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+fn pull_failed(image: &str, reason: String) -> anyhow::Error {
+    Error::PullFailed {
+        image: image.into(),
+        reason,
+    }
+    .into()
+}
+
+/// How long the engine might take to report a starting server as unhealthy
+///
+/// Unset values fall back to the defaults of Docker, 30s interval and 3 retries.
+fn health_check_duration(health_check: &HealthConfig) -> Duration {
+    let nanoseconds = |value: Option<i64>, default: u64| {
+        value
+            .filter(|value| *value > 0)
+            .map_or(default, |value| value as u64)
+    };
+    let start_period = nanoseconds(health_check.start_period, 0);
+    let interval = nanoseconds(health_check.interval, 30_000_000_000);
+    let retries = health_check.retries.filter(|r| *r > 0).unwrap_or(3) as u64;
+    Duration::from_nanos(start_period + interval * retries)
+}
+
+/// Picks a free port on the host that is not in `taken`
+///
+/// Ports are only bound once the container starts, so the same port could be picked twice.
+fn pick_port(taken: &[u16]) -> Result<u16> {
+    iter::repeat_with(pick_unused_port)
+        .take(10)
+        .flatten()
+        .find(|port| !taken.contains(port))
+        .ok_or_else(|| Error::PortUnavailable("Did not find any open port".into()).into())
+}
+
+fn random_password() -> String {
+    use rand::distributions::Alphanumeric;
+    use rand::Rng;
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(24)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bollard::models::PortTypeEnum;
+
+    use super::*;
+    use crate::engine::fake::{FakeContainer, FakeEngine};
+    use crate::GameName;
+
+    fn container(name: &str, image: &str, labels: &[&str]) -> FakeContainer {
+        FakeContainer::new(
+            name,
+            Config {
+                image: Some(image.into()),
+                labels: Some(
+                    labels
+                        .iter()
+                        .map(|label| (label.to_string(), String::new()))
+                        .collect(),
+                ),
+                ..Default::default()
+            },
+        )
+    }
+
+    async fn names(manager: &ServerManager<FakeEngine>, filter: ServerFilter) -> Vec<String> {
+        let mut names: Vec<_> = manager
+            .list(&filter)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|server| server.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn list_filters_servers() {
+        let engine = FakeEngine::default();
+        engine.insert(container(
+            "survival",
+            "itzg/minecraft-server",
+            &["dgs", "dgs-friends"],
+        ));
+        engine.insert(container("creative", "itzg/minecraft-server", &["dgs"]));
+        engine.insert(container(
+            "factory",
+            "factoriotools/factorio:stable",
+            &["dgs", "dgs-friends"],
+        ));
+        // Not created by dgs
+        engine.insert(container("unmanaged", "itzg/minecraft-server", &[]));
+        let id = engine.insert(container("web", "nginx", &["dgs"]));
+        engine.start(&id).await.unwrap();
+        let manager = ServerManager::new(engine);
+
+        assert_eq!(
+            names(&manager, ServerFilter::default()).await,
+            ["/creative", "/factory", "/survival"]
+        );
+        assert_eq!(
+            names(
+                &manager,
+                ServerFilter {
+                    tags: vec!["Friends".parse().unwrap()],
+                    ..Default::default()
+                }
+            )
+            .await,
+            ["/factory", "/survival"]
+        );
+        assert_eq!(
+            names(
+                &manager,
+                ServerFilter {
+                    game: Some(GameName::Minecraft),
+                    tags: vec!["friends".into()],
+                    ..Default::default()
+                }
+            )
+            .await,
+            ["/survival"]
+        );
+        assert_eq!(
+            names(
+                &manager,
+                ServerFilter {
+                    name: Some("CREAT".into()),
+                    ..Default::default()
+                }
+            )
+            .await,
+            ["/creative"]
+        );
+        assert!(names(
+            &manager,
+            ServerFilter {
+                state: Some(ContainerStateStatusEnum::RUNNING),
+                ..Default::default()
+            }
+        )
+        .await
+        .is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_checked_reports_invalid_servers() {
+        let engine = FakeEngine::default();
+        engine.insert(container("survival", "itzg/minecraft-server", &["dgs"]));
+        engine.insert(container("web", "nginx", &["dgs"]));
+        engine.insert(container("proxy", "nginx", &[]));
+        let manager = ServerManager::new(engine);
+
+        let (valid, invalid) = manager
+            .list_checked(&ServerFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(valid.len(), 1);
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].name(), "/web");
+        assert!(invalid[0].reason.contains("nginx"));
+
+        let (_, invalid) = manager
+            .list_checked(&ServerFilter {
+                name: Some("surv".into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(invalid.is_empty());
+    }
+
+    #[tokio::test]
+    async fn adopt_recreates_with_labels() {
+        let engine = FakeEngine::default();
+        let id = engine.insert(container("mc", "itzg/minecraft-server:java17", &["owner"]));
+        engine.start(&id).await.unwrap();
+        engine.insert(container("web", "nginx", &[]));
+        let manager = ServerManager::new(engine);
+
+        assert!(manager.adopt("web", None, &[]).await.is_err());
+        assert_eq!(manager.engine().containers().len(), 2);
+
+        let server = manager
+            .adopt("mc", Some("survival"), &["friends".into()])
+            .await
+            .unwrap();
+        assert_eq!(server.name, "/survival");
+        assert_eq!(server.tags, vec!["friends".to_string()]);
+        assert_eq!(server.status, ContainerStateStatusEnum::RUNNING);
+        assert_eq!(manager.engine().containers().len(), 2);
+        let labels = manager.engine().containers()[1].config.labels.clone();
+        assert!(labels.unwrap().contains_key("owner"));
+
+        assert!(manager.adopt("survival", None, &[]).await.is_err());
+
+        // docker-compose keeps the image as written in the compose file
+        manager.engine().insert(container(
+            "mc-compose_minecraft_1",
+            "itzg/minecraft-server",
+            &["com.docker.compose.project"],
+        ));
+        let server = manager
+            .adopt("mc-compose_minecraft_1", None, &[])
+            .await
+            .unwrap();
+        assert_eq!(server.game.name, GameName::Minecraft);
+    }
+
+    #[tokio::test]
+    async fn find_prefers_exact_names() {
+        let engine = FakeEngine::default();
+        engine.insert(container("survival", "itzg/minecraft-server", &["dgs"]));
+        engine.insert(container("survival-2", "itzg/minecraft-server", &["dgs"]));
+        let manager = ServerManager::new(engine);
+
+        assert_eq!(manager.find("Survival").await.unwrap().name, "/survival");
+        assert_eq!(manager.find("-2").await.unwrap().name, "/survival-2");
+        assert!(matches!(
+            manager.find("surv").await.unwrap_err().downcast_ref(),
+            Some(Error::AmbiguousName { matches, .. }) if matches.len() == 2
+        ));
+        assert!(matches!(
+            manager.find("creative").await.unwrap_err().downcast_ref(),
+            Some(Error::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn tmp_servers_are_removed_when_unhealthy() {
+        let game = Game::find_by_name("valheim").unwrap();
+        let manager =
+            ServerManager::new(FakeEngine::default().with_health(HealthStatusEnum::UNHEALTHY));
+        let server = manager.create(game, GameOptions::default()).await.unwrap();
+        assert_eq!(manager.engine().containers().len(), 1);
+
+        assert!(manager.start_healthy(&server.id, None).await.is_err());
+        assert!(manager.engine().containers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn tmp_servers_are_removed_when_not_ready_in_time() {
+        let game = Game::find_by_name("valheim").unwrap();
+        let manager =
+            ServerManager::new(FakeEngine::default().with_health(HealthStatusEnum::STARTING));
+        let server = manager.create(game, GameOptions::default()).await.unwrap();
+
+        let time_out = Duration::from_millis(100);
+        let error = manager
+            .start_healthy(&server.id, Some(time_out))
+            .await
+            .unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(Error::NotReady(t)) if *t == time_out));
+        assert!(manager.engine().containers().is_empty());
+
+        // The start period plus three retries every 10s
+        let health_check = game.health_check.as_ref().unwrap();
+        assert_eq!(
+            health_check_duration(&health_check.config()),
+            Duration::from_secs(health_check.start_period + 30)
+        );
+    }
+
+    #[tokio::test]
+    async fn tmp_servers_keep_running_when_healthy() {
+        let game = Game::find_by_name("valheim").unwrap();
+        let manager = ServerManager::new(FakeEngine::default());
+        let server = manager.create(game, GameOptions::default()).await.unwrap();
+
+        manager.start_healthy(&server.id, None).await.unwrap();
+        let servers = manager.list(&ServerFilter::default()).await.unwrap();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].status, ContainerStateStatusEnum::RUNNING);
+        assert_eq!(servers[0].health, Some(HealthStatusEnum::HEALTHY));
+        assert_eq!(servers[0].public_port(2456, PortTypeEnum::UDP), Some(2456));
+
+        manager.stop(&server.id).await.unwrap();
+        manager.remove(&server.id).await.unwrap();
+        assert!(manager.engine().containers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn apply_reconciles_manifest() {
+        let manager = ServerManager::new(FakeEngine::default());
+        let manifest = Manifest::parse(
+            r#"
+            [[server]]
+            name = "viking"
+            game = "valheim"
+            "#,
+        )
+        .unwrap();
+        let plan = manager.plan(&manifest, false).await.unwrap();
+        assert!(matches!(plan.changes[..], [Change::Create(_)]));
+        for change in &plan.changes {
+            manager.apply(change).await.unwrap();
+        }
+        assert!(manager.plan(&manifest, false).await.unwrap().is_empty());
+
+        let manifest = Manifest::parse(
+            r#"
+            [[server]]
+            name = "viking"
+            game = "valheim"
+            settings = { SERVER_PUBLIC = "false" }
+            "#,
+        )
+        .unwrap();
+        let plan = manager.plan(&manifest, false).await.unwrap();
+        assert!(matches!(
+            &plan.changes[..],
+            [Change::Recreate { changed, .. }] if changed == &["settings".to_string()]
+        ));
+        manager.apply(&plan.changes[0]).await.unwrap();
+        let containers = manager.engine().containers();
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0].name, "viking");
+        assert_eq!(containers[0].state, ContainerStateStatusEnum::RUNNING);
+
+        let manifest = Manifest::default();
+        assert!(manager.plan(&manifest, false).await.unwrap().is_empty());
+        let plan = manager.plan(&manifest, true).await.unwrap();
+        assert!(matches!(plan.changes[..], [Change::Remove(_)]));
+        manager.apply(&plan.changes[0]).await.unwrap();
+        assert!(manager.engine().containers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn apply_restores_server_when_recreating_fails() {
+        let manager = ServerManager::new(FakeEngine::default());
+        let manifest = Manifest::parse(
+            r#"
+            [[server]]
+            name = "viking"
+            game = "valheim"
+            "#,
+        )
+        .unwrap();
+        let plan = manager.plan(&manifest, false).await.unwrap();
+        manager.apply(&plan.changes[0]).await.unwrap();
+        let id = manager.engine().containers()[0].id.clone();
+
+        let manifest = Manifest::parse(
+            r#"
+            [[server]]
+            name = "viking"
+            game = "valheim"
+            settings = { SERVER_PUBLIC = "false" }
+            "#,
+        )
+        .unwrap();
+        let plan = manager.plan(&manifest, false).await.unwrap();
+        manager.engine().fail_next_create();
+        assert!(manager.apply(&plan.changes[0]).await.is_err());
+        let containers = manager.engine().containers();
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0].id, id);
+        assert_eq!(containers[0].name, "viking");
+        assert_eq!(containers[0].state, ContainerStateStatusEnum::RUNNING);
+        assert_eq!(
+            manager.plan(&manifest, false).await.unwrap().changes.len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn recreating_keeps_ports() {
+        let manager = ServerManager::new(FakeEngine::default());
+        let manifest = |memory| {
+            Manifest::parse(&format!(
+                r#"
+                [[server]]
+                name = "survival"
+                game = "minecraft"
+                memory = "{}"
+                accept_eula = true
+                "#,
+                memory
+            ))
+            .unwrap()
+        };
+        for change in manager.plan(&manifest("2G"), false).await.unwrap().changes {
+            manager.apply(&change).await.unwrap();
+        }
+        let port = |servers: Vec<BasicServerInfo>| servers[0].public_port(25565, PortTypeEnum::TCP);
+        let before = port(manager.list(&ServerFilter::default()).await.unwrap());
+
+        let plan = manager.plan(&manifest("4G"), false).await.unwrap();
+        assert!(matches!(plan.changes[..], [Change::Recreate { .. }]));
+        manager.apply(&plan.changes[0]).await.unwrap();
+        let after = port(manager.list(&ServerFilter::default()).await.unwrap());
+        assert!(before.is_some());
+        assert_eq!(before, after);
+    }
+
+    #[tokio::test]
+    async fn licensed_games_need_acceptance() {
+        let game = Game::find_by_name("minecraft").unwrap();
+        let manager = ServerManager::new(FakeEngine::default());
+        assert!(manager.create(game, GameOptions::default()).await.is_err());
+        assert!(manager.engine().containers().is_empty());
+    }
+}
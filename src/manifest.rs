@@ -0,0 +1,192 @@
+//! Declarative lists of servers that `dgs apply` makes an engine match
+//!
+//! ```toml
+//! [[server]]
+//! name = "survival"
+//! game = "minecraft"
+//! version = "1.17.1"
+//! tags = ["friends"]
+//! ports = [25565]
+//! volumes = ["survival:/data"]
+//! memory = "4G"
+//!
+//! [server.settings]
+//! DIFFICULTY = "hard"
+//! ```
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::resources::{Limits, Memory, RestartPolicy};
+use crate::{BasicServerInfo, Game, GameOptions, ServerSetup};
+
+/// Label the entry a server was created from is stored in as JSON
+pub const MANIFEST_LABEL: &str = "dgs.manifest";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(rename = "server", default)]
+    pub servers: Vec<ServerManifest>,
+}
+
+impl Manifest {
+    /// Parses a manifest and checks every entry
+    pub fn parse(toml: &str) -> Result<Self> {
+        let manifest: Self = toml::from_str(toml)?;
+        let mut names = HashSet::new();
+        for server in &manifest.servers {
+            if !names.insert(server.name.to_lowercase()) {
+                bail!("The server `{}` is listed more than once", server.name);
+            }
+            server
+                .setup()
+                .with_context(|| format!("Invalid server `{}`", server.name))?;
+        }
+        Ok(manifest)
+    }
+}
+
+/// A single `[[server]]` of a manifest
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServerManifest {
+    pub name: String,
+    pub game: String,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Host ports in the order of the game's ports, free ports are picked for the rest
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    /// Environment variables of the image e.g. `DIFFICULTY = "hard"`
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+    /// Volumes in the `docker run -v` syntax e.g. `survival:/data`
+    #[serde(default)]
+    pub volumes: Vec<String>,
+    /// e.g. `2G` or `512M`
+    pub memory: Option<String>,
+    pub cpus: Option<f64>,
+    /// Defaults to `unless-stopped`, so servers come back after a reboot
+    pub restart: Option<RestartPolicy>,
+    /// Accepting the license does not change the server, so it is not compared
+    #[serde(default, skip_serializing)]
+    pub accept_eula: bool,
+}
+
+impl ServerManifest {
+    /// Everything [`ServerManager::create_with`](crate::ServerManager::create_with) needs
+    pub fn setup(&self) -> Result<(&'static Game, GameOptions, ServerSetup)> {
+        let game = Game::find_by_name(&self.game)
+            .ok_or_else(|| anyhow!("Unable to find a game matching `{}`", self.game))?;
+        let options = GameOptions {
+            version: self.version.clone(),
+            limits: Limits {
+                memory: self
+                    .memory
+                    .as_deref()
+                    .map(str::parse::<Memory>)
+                    .transpose()?,
+                cpus: self.cpus,
+                restart: Some(self.restart.unwrap_or(RestartPolicy::UnlessStopped)),
+            },
+            accept_eula: self.accept_eula,
+            ..Default::default()
+        };
+        let mut labels = HashMap::new();
+        labels.insert(MANIFEST_LABEL.to_string(), serde_json::to_string(self)?);
+        let setup = ServerSetup {
+            name: Some(self.name.clone()),
+            tags: self.tags.clone(),
+            ports: self.ports.clone(),
+            envs: self
+                .settings
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect(),
+            volumes: self.volumes.clone(),
+            labels,
+        };
+        Ok((game, options, setup))
+    }
+
+    /// The fields that differ from the entry stored in `label`
+    ///
+    /// Servers not created from a manifest differ in every field.
+    pub fn changed(&self, label: Option<&str>) -> Vec<String> {
+        let old = label
+            .and_then(|label| serde_json::from_str(label).ok())
+            .unwrap_or(serde_json::Value::Null);
+        let new = serde_json::to_value(self).unwrap_or_default();
+        new.as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(key, value)| old.get(key) != Some(value))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+}
+
+/// What `dgs apply` does to a single server
+#[derive(Debug)]
+pub enum Change {
+    Create(ServerManifest),
+    /// Servers cannot be changed in place, so they are replaced
+    Recreate {
+        server: BasicServerInfo,
+        manifest: ServerManifest,
+        changed: Vec<String>,
+    },
+    /// Only servers created from a manifest are removed
+    Remove(BasicServerInfo),
+    Unchanged(BasicServerInfo),
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Create(manifest) => {
+                write!(f, "+ create `{}` ({})", manifest.name, manifest.game)
+            }
+            Change::Recreate {
+                manifest, changed, ..
+            } => write!(
+                f,
+                "~ recreate `{}` (changed: {})",
+                manifest.name,
+                changed
+                    .iter()
+                    .map(String::as_str)
+                    .intersperse(", ")
+                    .collect::<String>()
+            ),
+            Change::Remove(server) => write!(
+                f,
+                "- remove `{}` ({})",
+                server.name.trim_start_matches('/'),
+                server.game.name
+            ),
+            Change::Unchanged(server) => {
+                write!(f, "= unchanged `{}`", server.name.trim_start_matches('/'))
+            }
+        }
+    }
+}
+
+/// The changes needed to match a manifest, in the order of the manifest
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    /// Whether applying the plan would not change anything
+    pub fn is_empty(&self) -> bool {
+        self.changes
+            .iter()
+            .all(|change| matches!(change, Change::Unchanged(_)))
+    }
+}
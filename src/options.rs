@@ -0,0 +1,57 @@
+use anyhow::{bail, Result};
+use clap::Clap;
+
+use crate::games::factorio::FactorioOptions;
+use crate::games::minecraft::MinecraftOptions;
+use crate::games::valheim::ValheimOptions;
+use crate::resources::Limits;
+use crate::{Game, GameName};
+
+/// Everything that can be configured when creating a server
+#[derive(Clap, Default)]
+pub struct GameOptions {
+    #[clap(long, short)]
+    pub version: Option<String>,
+    #[clap(flatten)]
+    pub limits: Limits,
+    /// Player allowed to join, can be repeated
+    #[clap(long = "whitelist")]
+    pub whitelist: Vec<String>,
+    /// Player with operator rights, can be repeated
+    #[clap(long = "op")]
+    pub ops: Vec<String>,
+    /// Accept the license of the game without being asked, e.g. the Minecraft EULA
+    #[clap(long)]
+    pub accept_eula: bool,
+    #[clap(flatten)]
+    pub minecraft: MinecraftOptions,
+    #[clap(flatten)]
+    pub factorio: FactorioOptions,
+    #[clap(flatten)]
+    pub valheim: ValheimOptions,
+}
+
+impl GameOptions {
+    /// Checks the options only supported by some games before anything is pulled
+    pub fn validate(&self, game: &Game) -> Result<()> {
+        if !self.minecraft.is_empty() {
+            if game.name != GameName::Minecraft {
+                bail!("`--type`, `--modpack` and `--mods` are only supported by Minecraft");
+            }
+            self.minecraft.envs()?;
+        }
+        if !self.factorio.is_empty() {
+            if game.name != GameName::Factorio {
+                bail!("`--mod`, `--save` and `--generate-new-save` are only supported by Factorio");
+            }
+            self.factorio.files()?;
+        }
+        if !self.valheim.is_empty() {
+            if game.name != GameName::Valheim {
+                bail!("`--server-name`, `--world-name`, `--password`, `--private`, `--bepinex` and `--valheim-plus` are only supported by Valheim");
+            }
+            self.valheim.validate()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! Client for the Valve A2S server queries spoken by most Steam games
+//!
+//! See <https://developer.valvesoftware.com/wiki/Server_queries> for the protocol.
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+use super::ServerStatus;
+
+/// Every packet that is not split starts with this header
+const SINGLE_PACKET: [u8; 4] = [0xFF; 4];
+const SPLIT_PACKET: [u8; 4] = [0xFE, 0xFF, 0xFF, 0xFF];
+
+const A2S_INFO: u8 = 0x54;
+const A2S_PLAYER: u8 = 0x55;
+const S2A_INFO: u8 = 0x49;
+const S2A_PLAYER: u8 = 0x44;
+const S2C_CHALLENGE: u8 = 0x41;
+
+/// The maximum size of a packet that is not split
+const MAX_PACKET_SIZE: usize = 1400;
+
+/// How long to wait for the players, well within the timeout of the whole query
+/// so the info is not lost
+const PLAYER_TIME_OUT: Duration = Duration::from_millis(500);
+
+/// Requests the server info and players of the server listening on `address:port`
+///
+/// The player names are left empty if the server does not answer the player
+/// query in time, as some servers disable it.
+pub async fn status(address: &str, port: u16) -> Result<ServerStatus> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect((address, port)).await?;
+
+    let mut status = info(&socket).await?;
+    if let Ok(Ok(players)) = timeout(PLAYER_TIME_OUT, players(&socket)).await {
+        status.player_names = players;
+    }
+    Ok(status)
+}
+
+/// Sends the `A2S_INFO` query
+async fn info(socket: &UdpSocket) -> Result<ServerStatus> {
+    let mut payload = b"Source Engine Query\0".to_vec();
+    let response = request(socket, A2S_INFO, &mut payload, S2A_INFO).await?;
+    let mut reader = Reader(&response);
+    let _protocol = reader.u8()?;
+    let name = reader.string()?;
+    let map = reader.string()?;
+    let _folder = reader.string()?;
+    let _game = reader.string()?;
+    let _app_id = reader.bytes(2)?;
+    let players = reader.u8()?;
+    let max_players = reader.u8()?;
+    let _bots = reader.u8()?;
+    let _server_type = reader.u8()?;
+    let _environment = reader.u8()?;
+    let _visibility = reader.u8()?;
+    let _vac = reader.u8()?;
+    let version = reader.string()?;
+    Ok(ServerStatus {
+        name: Some(name),
+        map: Some(map),
+        version: Some(version),
+        players: players.into(),
+        max_players: max_players.into(),
+        ..Default::default()
+    })
+}
+
+/// Sends the `A2S_PLAYER` query and returns the names of all players
+async fn players(socket: &UdpSocket) -> Result<Vec<String>> {
+    // The server answers the initial `-1` challenge with the real one
+    let mut payload = vec![0xFF; 4];
+    let response = request(socket, A2S_PLAYER, &mut payload, S2A_PLAYER).await?;
+    let mut reader = Reader(&response);
+    let count = reader.u8()?;
+    let mut names = Vec::with_capacity(count.into());
+    for _ in 0..count {
+        let _index = reader.u8()?;
+        names.push(reader.string()?);
+        let _score = reader.bytes(4)?;
+        let _duration = reader.bytes(4)?;
+    }
+    // Players that are still connecting have no name yet
+    names.retain(|name| !name.is_empty());
+    Ok(names)
+}
+
+/// Sends a query and answers a challenge if the server sends one
+///
+/// Returns the response without the header and type.
+async fn request(
+    socket: &UdpSocket,
+    typ: u8,
+    payload: &mut Vec<u8>,
+    expected: u8,
+) -> Result<Vec<u8>> {
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    // A server should not send more than one challenge, but anything is possible
+    for _ in 0..2 {
+        let mut packet = SINGLE_PACKET.to_vec();
+        packet.push(typ);
+        packet.extend_from_slice(payload);
+        socket.send(&packet).await?;
+
+        let length = socket.recv(&mut buffer).await?;
+        let response = &buffer[..length];
+        if response.starts_with(&SPLIT_PACKET) {
+            bail!("Split responses are not supported");
+        }
+        let response = response
+            .strip_prefix(&SINGLE_PACKET[..])
+            .ok_or_else(|| anyhow!("Invalid response header"))?;
+        match response.split_first() {
+            Some((&S2C_CHALLENGE, challenge)) => {
+                let challenge = challenge
+                    .get(..4)
+                    .ok_or_else(|| anyhow!("Challenge is too short"))?;
+                // `A2S_PLAYER` replaces the placeholder, `A2S_INFO` appends the challenge
+                if typ == A2S_PLAYER {
+                    payload.clear();
+                }
+                payload.extend_from_slice(challenge);
+            }
+            Some((&kind, response)) if kind == expected => return Ok(response.to_vec()),
+            Some((kind, _)) => bail!("Unexpected response type `{:#x}`", kind),
+            None => bail!("Empty response"),
+        }
+    }
+    bail!("The server keeps sending challenges")
+}
+
+/// Reads the fields of a response
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        if self.0.len() < count {
+            bail!("The response ended early");
+        }
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// Reads a null terminated string
+    fn string(&mut self) -> Result<String> {
+        let end = self
+            .0
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| anyhow!("Unterminated string in response"))?;
+        let string = String::from_utf8_lossy(&self.0[..end]).into_owned();
+        self.0 = &self.0[end + 1..];
+        Ok(string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every query with a challenge first, like servers since late 2020
+    async fn fake_server(socket: UdpSocket) {
+        let challenge = [1, 2, 3, 4];
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            let (length, client) = socket.recv_from(&mut buffer).await.unwrap();
+            let request = buffer[..length].strip_prefix(&SINGLE_PACKET[..]).unwrap();
+            let mut response = SINGLE_PACKET.to_vec();
+            match request.split_first().unwrap() {
+                (&A2S_INFO, payload) if payload.ends_with(&challenge) => {
+                    assert!(payload.starts_with(b"Source Engine Query\0"));
+                    response.push(S2A_INFO);
+                    response.push(17);
+                    response.extend_from_slice(b"Viking Hall\0Dedicated\0valheim\0Valheim\0");
+                    response.extend_from_slice(&[0, 0, 2, 10, 0, b'd', b'l', 0, 0]);
+                    response.extend_from_slice(b"0.202.14\0");
+                }
+                (&A2S_PLAYER, payload) if payload == challenge => {
+                    response.extend_from_slice(&[S2A_PLAYER, 3]);
+                    for name in ["Odin", "", "Thor"] {
+                        response.push(0);
+                        response.extend_from_slice(name.as_bytes());
+                        response.extend_from_slice(&[0; 9]);
+                    }
+                }
+                _ => {
+                    response.push(S2C_CHALLENGE);
+                    response.extend_from_slice(&challenge);
+                }
+            }
+            socket.send_to(&response, client).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_challenges() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let server = tokio::spawn(fake_server(socket));
+
+        let status = status("127.0.0.1", port).await.unwrap();
+        server.abort();
+        assert_eq!(status.name.as_deref(), Some("Viking Hall"));
+        assert_eq!(status.map.as_deref(), Some("Dedicated"));
+        assert_eq!(status.version.as_deref(), Some("0.202.14"));
+        assert_eq!((status.players, status.max_players), (2, 10));
+        assert_eq!(status.player_names, ["Odin", "Thor"]);
+    }
+}
@@ -0,0 +1,234 @@
+//! Client for the Minecraft Server List Ping
+//!
+//! See <https://wiki.vg/Server_List_Ping> for the protocol.
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::ServerStatus;
+
+/// The status works with any version, `-1` is the convention when it is unknown
+const PROTOCOL_VERSION: i32 = -1;
+/// The next state requested in the handshake
+const STATE_STATUS: i32 = 1;
+/// The largest packet possible with a 3 byte length
+const MAX_PACKET_LENGTH: usize = (1 << 21) - 1;
+
+#[derive(Deserialize)]
+struct Response {
+    version: ResponseVersion,
+    players: ResponsePlayers,
+    #[serde(default)]
+    description: Description,
+}
+
+#[derive(Deserialize)]
+struct ResponseVersion {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ResponsePlayers {
+    max: u32,
+    online: u32,
+    #[serde(default)]
+    sample: Vec<ResponsePlayer>,
+}
+
+#[derive(Deserialize)]
+struct ResponsePlayer {
+    name: String,
+}
+
+/// A chat component, either plain text or an object with nested components
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Description {
+    Text(String),
+    Component {
+        #[serde(default)]
+        text: String,
+        #[serde(default)]
+        extra: Vec<Description>,
+    },
+}
+
+impl Default for Description {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+impl Description {
+    /// The text without any formatting
+    fn plain(&self) -> String {
+        let text = match self {
+            Description::Text(text) => text.clone(),
+            Description::Component { text, extra } => {
+                text.clone() + &extra.iter().map(Self::plain).collect::<String>()
+            }
+        };
+        // Legacy formatting codes are `§` followed by a single character
+        let mut chars = text.chars();
+        let mut plain = String::with_capacity(text.len());
+        while let Some(c) = chars.next() {
+            if c == '§' {
+                chars.next();
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+}
+
+impl Response {
+    fn into_status(self) -> ServerStatus {
+        ServerStatus {
+            motd: Some(self.description.plain()),
+            version: Some(self.version.name),
+            players: self.players.online,
+            max_players: self.players.max,
+            player_names: self
+                .players
+                .sample
+                .into_iter()
+                .map(|player| player.name)
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Requests the status of the server listening on `address:port`
+pub async fn status(address: &str, port: u16) -> Result<ServerStatus> {
+    let mut stream = TcpStream::connect((address, port)).await?;
+    status_with(&mut stream, address, port).await
+}
+
+/// Requests the status over an already established connection
+///
+/// `address` and `port` are only sent to the server as part of the handshake.
+pub async fn status_with<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    address: &str,
+    port: u16,
+) -> Result<ServerStatus> {
+    let mut handshake = Vec::new();
+    write_var_int(&mut handshake, 0x00);
+    write_var_int(&mut handshake, PROTOCOL_VERSION);
+    write_string(&mut handshake, address);
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_var_int(&mut handshake, STATE_STATUS);
+    write_packet(stream, &handshake).await?;
+    // The status request has no fields
+    write_packet(stream, &[0x00]).await?;
+    stream.flush().await?;
+
+    let length = read_var_int(stream).await?;
+    if length <= 0 || length as usize > MAX_PACKET_LENGTH {
+        bail!("Invalid packet length `{}`", length);
+    }
+    let mut packet = vec![0; length as usize];
+    stream.read_exact(&mut packet).await?;
+
+    let mut packet = &packet[..];
+    let id = read_var_int(&mut packet).await?;
+    if id != 0x00 {
+        bail!("Expected a status response but got packet `{:#x}`", id);
+    }
+    let json_length = read_var_int(&mut packet).await?;
+    let json = packet
+        .get(..json_length.max(0) as usize)
+        .ok_or_else(|| anyhow!("The status response is shorter than announced"))?;
+    let response: Response =
+        serde_json::from_slice(json).map_err(|e| anyhow!("Invalid status response: {}", e))?;
+    Ok(response.into_status())
+}
+
+fn write_var_int(buffer: &mut Vec<u8>, value: i32) {
+    // Negative values are sent in two's complement
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buffer.push(value as u8);
+            return;
+        }
+        buffer.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    write_var_int(buffer, value.len() as i32);
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+async fn write_packet<W: AsyncWrite + Unpin>(writer: &mut W, packet: &[u8]) -> Result<()> {
+    let mut buffer = Vec::with_capacity(packet.len() + 5);
+    write_var_int(&mut buffer, packet.len() as i32);
+    buffer.extend_from_slice(packet);
+    Ok(writer.write_all(&buffer).await?)
+}
+
+async fn read_var_int<R: AsyncRead + Unpin>(reader: &mut R) -> Result<i32> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = reader.read_u8().await?;
+        value |= u32::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    bail!("VarInt is longer than 5 bytes")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, DuplexStream};
+
+    use super::*;
+
+    async fn read_packet(stream: &mut DuplexStream) -> Vec<u8> {
+        let length = read_var_int(stream).await.unwrap();
+        let mut packet = vec![0; length as usize];
+        stream.read_exact(&mut packet).await.unwrap();
+        packet
+    }
+
+    #[tokio::test]
+    async fn parses_status_response() {
+        let (mut client, mut server) = duplex(1024);
+        let fake_server = async move {
+            let handshake = read_packet(&mut server).await;
+            let mut fields = &handshake[..];
+            assert_eq!(read_var_int(&mut fields).await.unwrap(), 0x00);
+            assert_eq!(read_var_int(&mut fields).await.unwrap(), PROTOCOL_VERSION);
+            // Address, then the port and the requested state
+            assert!(fields.starts_with(b"\x09localhost"));
+            assert!(fields.ends_with(&[0x63, 0xDD, STATE_STATUS as u8]));
+            assert_eq!(read_packet(&mut server).await, vec![0x00_u8]);
+
+            let mut response = Vec::new();
+            write_var_int(&mut response, 0x00);
+            write_string(
+                &mut response,
+                r#"{
+                    "version": {"name": "Paper 1.17.1", "protocol": 756},
+                    "players": {"max": 20, "online": 2, "sample": [{"name": "Alex", "id": "0"}]},
+                    "description": {"text": "§aWelcome ", "extra": [{"text": "home"}]}
+                }"#,
+            );
+            write_packet(&mut server, &response).await.unwrap();
+        };
+
+        let (status, ()) = tokio::join!(status_with(&mut client, "localhost", 25565), fake_server);
+        let status = status.unwrap();
+        assert_eq!(status.version.as_deref(), Some("Paper 1.17.1"));
+        assert_eq!(status.motd.as_deref(), Some("Welcome home"));
+        assert_eq!(status.players, 2);
+        assert_eq!(status.max_players, 20);
+        assert_eq!(status.player_names, vec!["Alex".to_string()]);
+    }
+}
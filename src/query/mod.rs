@@ -0,0 +1,69 @@
+pub mod a2s;
+pub mod minecraft;
+
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use bollard::models::PortTypeEnum;
+use tokio::time::timeout;
+
+use crate::{BasicServerInfo, QueryProtocol};
+
+/// Seconds to wait for a game server to answer a query
+const QUERY_TIME_OUT: u64 = 2;
+
+/// What a game server reports about itself
+#[derive(Debug, Clone, Default)]
+pub struct ServerStatus {
+    pub name: Option<String>,
+    pub motd: Option<String>,
+    /// The map or world
+    pub map: Option<String>,
+    pub version: Option<String>,
+    pub players: u32,
+    pub max_players: u32,
+    /// Not every server reports all players
+    pub player_names: Vec<String>,
+}
+
+impl Display for ServerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "players: {}/{}", self.players, self.max_players)?;
+        if !self.player_names.is_empty() {
+            write!(f, " ({})", self.player_names.join(", "))?;
+        }
+        writeln!(f)?;
+        if let Some(name) = &self.name {
+            writeln!(f, "name: {}", name)?;
+        }
+        if let Some(map) = &self.map {
+            writeln!(f, "map: {}", map)?;
+        }
+        if let Some(version) = &self.version {
+            writeln!(f, "version: {}", version)?;
+        }
+        if let Some(motd) = &self.motd {
+            writeln!(f, "motd: {}", motd)?;
+        }
+        Ok(())
+    }
+}
+
+/// Asks the game running on `server` for its status
+///
+/// Returns `None` for games dgs cannot query.
+pub async fn query(server: &BasicServerInfo, address: &str) -> Option<Result<ServerStatus>> {
+    let time_out = Duration::from_secs(QUERY_TIME_OUT);
+    let status = match server.game.query? {
+        QueryProtocol::MinecraftPing(port) => {
+            let port = server.public_port(port, PortTypeEnum::TCP)?;
+            timeout(time_out, minecraft::status(address, port)).await
+        }
+        QueryProtocol::A2s(port) => {
+            let port = server.public_port(port, PortTypeEnum::UDP)?;
+            timeout(time_out, a2s::status(address, port)).await
+        }
+    };
+    Some(status.unwrap_or_else(|_| Err(anyhow!("No answer after {}s", QUERY_TIME_OUT))))
+}
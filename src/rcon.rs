@@ -0,0 +1,163 @@
+//! Client for the Source RCON protocol
+//!
+//! See <https://developer.valvesoftware.com/wiki/Source_RCON_Protocol> for the protocol.
+use anyhow::{bail, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// Minecraft drops the connection on larger commands
+const MAX_COMMAND_LENGTH: usize = 1446;
+/// Generous limit for responses, to not allocate whatever a broken server sends
+const MAX_PACKET_SIZE: i32 = 1 << 20;
+
+pub struct Client<S> {
+    stream: S,
+    next_id: i32,
+}
+
+impl Client<TcpStream> {
+    pub async fn connect(address: &str, port: u16, password: &str) -> Result<Self> {
+        Self::authenticate(TcpStream::connect((address, port)).await?, password).await
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
+    pub async fn authenticate(stream: S, password: &str) -> Result<Self> {
+        let mut client = Self { stream, next_id: 1 };
+        let id = client.send(SERVERDATA_AUTH, password).await?;
+        loop {
+            // Some servers send an empty response value before the auth response
+            let (response_id, typ, _) = client.receive().await?;
+            if typ == SERVERDATA_AUTH_RESPONSE {
+                if response_id == -1 {
+                    bail!("The server rejected the RCON password");
+                } else if response_id == id {
+                    return Ok(client);
+                }
+            }
+        }
+    }
+
+    /// Runs `command` on the server and returns its output
+    ///
+    /// Long outputs are split into multiple packets. Servers answer packets in
+    /// order, so an empty packet is sent after the command and the output ends
+    /// with the answer to it.
+    pub async fn command(&mut self, command: &str) -> Result<String> {
+        if command.len() > MAX_COMMAND_LENGTH {
+            bail!("Commands can be at most {} bytes long", MAX_COMMAND_LENGTH);
+        }
+        let id = self.send(SERVERDATA_EXECCOMMAND, command).await?;
+        let end_id = self.send(SERVERDATA_RESPONSE_VALUE, "").await?;
+        let mut output = String::new();
+        loop {
+            let (response_id, typ, body) = self.receive().await?;
+            if response_id == end_id {
+                return Ok(output);
+            } else if response_id == id && typ == SERVERDATA_RESPONSE_VALUE {
+                output += &body;
+            }
+        }
+    }
+
+    async fn send(&mut self, typ: i32, body: &str) -> Result<i32> {
+        let id = self.next_id;
+        self.next_id += 1;
+        // Size excludes itself but includes id, type and both null bytes
+        let size = body.len() as i32 + 10;
+        let mut packet = Vec::with_capacity(size as usize + 4);
+        packet.extend_from_slice(&size.to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&typ.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        self.stream.write_all(&packet).await?;
+        Ok(id)
+    }
+
+    /// Returns id, type and body of the next packet
+    async fn receive(&mut self) -> Result<(i32, i32, String)> {
+        let size = self.stream.read_i32_le().await?;
+        if !(10..=MAX_PACKET_SIZE).contains(&size) {
+            bail!("Invalid RCON packet size `{}`", size);
+        }
+        let id = self.stream.read_i32_le().await?;
+        let typ = self.stream.read_i32_le().await?;
+        let mut body = vec![0; size as usize - 8];
+        self.stream.read_exact(&mut body).await?;
+        body.truncate(body.len() - 2);
+        Ok((id, typ, String::from_utf8_lossy(&body).into_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, DuplexStream};
+
+    use super::*;
+
+    async fn read_packet(stream: &mut DuplexStream) -> (i32, i32, String) {
+        let size = stream.read_i32_le().await.unwrap();
+        let id = stream.read_i32_le().await.unwrap();
+        let typ = stream.read_i32_le().await.unwrap();
+        let mut body = vec![0; size as usize - 8];
+        stream.read_exact(&mut body).await.unwrap();
+        assert!(body.ends_with(&[0, 0]));
+        body.truncate(body.len() - 2);
+        (id, typ, String::from_utf8(body).unwrap())
+    }
+
+    async fn write_packet(stream: &mut DuplexStream, id: i32, typ: i32, body: &str) {
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&typ.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        stream.write_all(&packet).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn joins_split_responses() {
+        let (client, mut server) = duplex(1 << 16);
+        let fake_server = async move {
+            let (id, typ, password) = read_packet(&mut server).await;
+            assert_eq!((typ, password.as_str()), (SERVERDATA_AUTH, "secret"));
+            write_packet(&mut server, id, SERVERDATA_RESPONSE_VALUE, "").await;
+            write_packet(&mut server, id, SERVERDATA_AUTH_RESPONSE, "").await;
+
+            let (id, typ, command) = read_packet(&mut server).await;
+            assert_eq!((typ, command.as_str()), (SERVERDATA_EXECCOMMAND, "help"));
+            let (end_id, typ, body) = read_packet(&mut server).await;
+            assert_eq!((typ, body.as_str()), (SERVERDATA_RESPONSE_VALUE, ""));
+            let first = "a".repeat(4096);
+            write_packet(&mut server, id, SERVERDATA_RESPONSE_VALUE, &first).await;
+            write_packet(&mut server, id, SERVERDATA_RESPONSE_VALUE, "/help [page]").await;
+            write_packet(&mut server, end_id, SERVERDATA_RESPONSE_VALUE, "").await;
+            server
+        };
+        let client = async move {
+            let mut client = Client::authenticate(client, "secret").await.unwrap();
+            client.command("help").await.unwrap()
+        };
+        let (_, output) = tokio::join!(fake_server, client);
+        assert_eq!(output, "a".repeat(4096) + "/help [page]");
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_password() {
+        let (client, mut server) = duplex(1024);
+        let fake_server = async move {
+            read_packet(&mut server).await;
+            write_packet(&mut server, -1, SERVERDATA_AUTH_RESPONSE, "").await;
+            server
+        };
+        let (_, client) = tokio::join!(fake_server, Client::authenticate(client, "wrong"));
+        assert!(client.is_err());
+    }
+}
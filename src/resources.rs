@@ -0,0 +1,232 @@
+use core::fmt;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+use bollard::container::Stats as ContainerStats;
+use bollard::models::{HostConfig, RestartPolicy as EngineRestartPolicy, RestartPolicyNameEnum};
+use clap::{ArgEnum, Clap};
+use serde::{Deserialize, Serialize};
+
+const KIB: u64 = 1024;
+const MIB: u64 = 1024 * KIB;
+const GIB: u64 = 1024 * MIB;
+
+/// An amount of memory in bytes
+///
+/// Parsed and displayed like the JVM's `-Xmx` e.g. `2G` or `512M`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Memory(pub u64);
+
+impl Memory {
+    pub const fn gib(gib: u64) -> Self {
+        Self(gib * GIB)
+    }
+}
+
+impl FromStr for Memory {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        let lower = lower.strip_suffix('b').unwrap_or(&lower);
+        let (number, unit) = match lower.char_indices().last() {
+            Some((i, 'k')) => (&lower[..i], KIB),
+            Some((i, 'm')) => (&lower[..i], MIB),
+            Some((i, 'g')) => (&lower[..i], GIB),
+            _ => (lower, 1),
+        };
+        number
+            .parse::<u64>()
+            .ok()
+            .and_then(|number| number.checked_mul(unit))
+            .map(Self)
+            .ok_or_else(|| anyhow!("Invalid amount of memory `{}`, expected e.g. `2G`", s))
+    }
+}
+
+impl Display for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => write!(f, "0"),
+            bytes if bytes % GIB == 0 => write!(f, "{}G", bytes / GIB),
+            bytes if bytes % MIB == 0 => write!(f, "{}M", bytes / MIB),
+            bytes if bytes % KIB == 0 => write!(f, "{}K", bytes / KIB),
+            bytes => write!(f, "{}", bytes),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ArgEnum, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    No,
+    OnFailure,
+    UnlessStopped,
+    Always,
+}
+
+impl Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", RestartPolicyNameEnum::from(*self))
+    }
+}
+
+impl From<RestartPolicy> for RestartPolicyNameEnum {
+    fn from(policy: RestartPolicy) -> Self {
+        match policy {
+            RestartPolicy::No => Self::NO,
+            RestartPolicy::OnFailure => Self::ON_FAILURE,
+            RestartPolicy::UnlessStopped => Self::UNLESS_STOPPED,
+            RestartPolicy::Always => Self::ALWAYS,
+        }
+    }
+}
+
+/// Resource limits and restart behavior of a server
+///
+/// Unset values are left to the engine.
+#[derive(Clap, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// Maximum memory the server may use e.g. `2G` or `512M`
+    #[clap(long, short)]
+    pub memory: Option<Memory>,
+    /// Number of CPUs the server may use e.g. `1.5`
+    #[clap(long)]
+    pub cpus: Option<f64>,
+    /// When the engine should restart the server
+    #[clap(long, arg_enum)]
+    pub restart: Option<RestartPolicy>,
+}
+
+impl Limits {
+    /// Takes every value not set in `self` from `defaults`
+    pub fn or(self, defaults: Self) -> Self {
+        Self {
+            memory: self.memory.or(defaults.memory),
+            cpus: self.cpus.or(defaults.cpus),
+            restart: self.restart.or(defaults.restart),
+        }
+    }
+
+    pub fn host_config(&self) -> HostConfig {
+        HostConfig {
+            memory: self.memory.map(|Memory(bytes)| bytes as i64),
+            nano_cpus: self.cpus.map(|cpus| (cpus * 1e9) as i64),
+            restart_policy: self.restart.map(|policy| EngineRestartPolicy {
+                name: Some(policy.into()),
+                maximum_retry_count: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// The limits set in the `host_config` of a container
+    pub fn from_host_config(host_config: HostConfig) -> Self {
+        Self {
+            memory: host_config
+                .memory
+                .filter(|memory| *memory > 0)
+                .map(|memory| Memory(memory as u64)),
+            cpus: host_config
+                .nano_cpus
+                .filter(|nano_cpus| *nano_cpus > 0)
+                .map(|nano_cpus| nano_cpus as f64 / 1e9),
+            restart: match host_config.restart_policy.and_then(|policy| policy.name) {
+                Some(RestartPolicyNameEnum::ALWAYS) => Some(RestartPolicy::Always),
+                Some(RestartPolicyNameEnum::UNLESS_STOPPED) => Some(RestartPolicy::UnlessStopped),
+                Some(RestartPolicyNameEnum::ON_FAILURE) => Some(RestartPolicy::OnFailure),
+                Some(RestartPolicyNameEnum::NO) | Some(RestartPolicyNameEnum::EMPTY) | None => None,
+            },
+        }
+    }
+}
+
+impl Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(memory) = self.memory {
+            writeln!(f, "memory: {}", memory)?;
+        }
+        if let Some(cpus) = self.cpus {
+            writeln!(f, "cpus: {}", cpus)?;
+        }
+        if let Some(restart) = self.restart {
+            writeln!(f, "restart: {}", restart)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resource usage of a running server
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    /// Percent of a single CPU, exceeds 100 when using multiple cores
+    pub cpu: f64,
+    pub memory: u64,
+    pub memory_limit: u64,
+    pub rx: u64,
+    pub tx: u64,
+    pub block_read: u64,
+    pub block_write: u64,
+}
+
+impl From<ContainerStats> for Usage {
+    fn from(stats: ContainerStats) -> Self {
+        // Calculated the same way as `docker stats`
+        let cpu_delta = stats
+            .cpu_stats
+            .cpu_usage
+            .total_usage
+            .saturating_sub(stats.precpu_stats.cpu_usage.total_usage);
+        let system_delta = stats
+            .cpu_stats
+            .system_cpu_usage
+            .unwrap_or_default()
+            .saturating_sub(stats.precpu_stats.system_cpu_usage.unwrap_or_default());
+        let online_cpus = stats.cpu_stats.online_cpus.unwrap_or_else(|| {
+            stats
+                .cpu_stats
+                .cpu_usage
+                .percpu_usage
+                .as_ref()
+                .map(|usage| usage.len() as u64)
+                .unwrap_or(1)
+        });
+        let cpu = if system_delta > 0 {
+            cpu_delta as f64 / system_delta as f64 * online_cpus as f64 * 100.
+        } else {
+            0.
+        };
+
+        let (rx, tx) = stats
+            .networks
+            .iter()
+            .flat_map(|networks| networks.values())
+            .fold((0, 0), |(rx, tx), network| {
+                (rx + network.rx_bytes, tx + network.tx_bytes)
+            });
+
+        let (block_read, block_write) = stats
+            .blkio_stats
+            .io_service_bytes_recursive
+            .iter()
+            .flatten()
+            .fold((0, 0), |(read, write), entry| {
+                match &*entry.op.to_lowercase() {
+                    "read" => (read + entry.value, write),
+                    "write" => (read, write + entry.value),
+                    _ => (read, write),
+                }
+            });
+
+        Self {
+            cpu,
+            memory: stats.memory_stats.usage.unwrap_or_default(),
+            memory_limit: stats.memory_stats.limit.unwrap_or_default(),
+            rx,
+            tx,
+            block_read,
+            block_write,
+        }
+    }
+}
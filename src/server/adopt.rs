@@ -0,0 +1,40 @@
+use anyhow::Result;
+use clap::Clap;
+use docker_game_servers::LowerCaseString;
+
+use crate::host::Host;
+
+#[derive(Clap)]
+pub struct Adopt {
+    /// Name or id of the container
+    container: String,
+    /// Name of the server, defaults to the name of the container
+    #[clap(short, long)]
+    name: Option<String>,
+    /// Tags to add to the server
+    ///
+    /// Usage: `-t first_tag -t second_tag`.
+    #[clap(short, long = "tag")]
+    tags: Vec<LowerCaseString>,
+}
+
+pub async fn adopt(
+    Adopt {
+        container,
+        name,
+        tags,
+    }: Adopt,
+    host: &Host,
+) -> Result<()> {
+    let server = host
+        .manager
+        .adopt(&container, name.as_deref(), &tags)
+        .await?;
+    println!(
+        "`{}` is now managed by dgs as the {} server `{}`",
+        container,
+        server.game.name,
+        server.name.trim_start_matches('/')
+    );
+    Ok(())
+}
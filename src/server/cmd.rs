@@ -0,0 +1,58 @@
+use anyhow::Result;
+use clap::Clap;
+use docker_game_servers::{BasicServerInfo, CommandDelivery, Error};
+use tokio::io::AsyncWriteExt;
+
+use super::rcon::connect;
+use crate::host::Host;
+
+#[derive(Clap)]
+pub struct Cmd {
+    /// Name of the server
+    name: String,
+    /// The line to send e.g. `"say restarting in 5 minutes"`
+    line: Vec<String>,
+}
+
+/// Sends `line` to the console of `server` and returns the output if there is any
+pub(super) async fn send(host: &Host, server: &BasicServerInfo, line: String) -> Result<String> {
+    Ok(match server.game.command_delivery {
+        Some(CommandDelivery::Exec(command)) => {
+            host.manager
+                .exec(
+                    &server.id,
+                    command
+                        .iter()
+                        .map(|arg| arg.to_string())
+                        .chain(Some(line))
+                        .collect(),
+                )
+                .await?
+        }
+        Some(CommandDelivery::Rcon) => connect(host, server).await?.command(&line).await?,
+        Some(CommandDelivery::Stdin) => {
+            let mut input = host.manager.attach_stdin(&server.id).await?;
+            input.write_all(format!("{}\n", line).as_bytes()).await?;
+            input.flush().await?;
+            // The output only shows up in the logs
+            String::new()
+        }
+        None => {
+            return Err(Error::unsupported(server.game.name, "sending console commands").into())
+        }
+    })
+}
+
+pub async fn cmd(Cmd { name, line }: Cmd, host: &Host) -> Result<()> {
+    let server = host.manager.find(&name).await?;
+    print_output(&send(host, &server, line.join(" ")).await?);
+    Ok(())
+}
+
+/// Prints the output of a console command, unless it is empty
+pub(super) fn print_output(output: &str) {
+    let output = output.trim_end();
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::{duration, ServerSpec, VolumeSource};
+
+#[derive(Serialize)]
+struct Compose {
+    services: BTreeMap<String, Service>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    volumes: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Serialize)]
+struct Service {
+    image: String,
+    container_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entrypoint: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    environment: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    env_file: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    ports: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    volumes: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    labels: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restart: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mem_limit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpus: Option<f64>,
+    #[serde(skip_serializing_if = "is_false")]
+    stdin_open: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    tty: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    healthcheck: Option<Healthcheck>,
+}
+
+#[derive(Serialize)]
+struct Healthcheck {
+    test: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retries: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_period: Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// A `docker-compose.yml` with a single service for the server
+///
+/// The secret envs are read from `env_file` next to it.
+pub fn compose(spec: &ServerSpec, env_file: Option<&str>) -> Result<String> {
+    let mut volumes = BTreeMap::new();
+    let service = Service {
+        image: spec.image.clone(),
+        container_name: spec.name.clone(),
+        entrypoint: spec.entrypoint.clone(),
+        command: spec.cmd.clone(),
+        environment: spec.env.clone(),
+        env_file: env_file.map(String::from),
+        ports: spec.ports.iter().map(ToString::to_string).collect(),
+        volumes: spec
+            .volumes
+            .iter()
+            .map(|volume| {
+                if let VolumeSource::Named(name) = &volume.source {
+                    volumes.insert(name.clone(), BTreeMap::new());
+                }
+                volume.to_string()
+            })
+            .collect(),
+        labels: spec.labels.clone(),
+        restart: spec.limits.restart.map(|restart| restart.to_string()),
+        mem_limit: spec
+            .limits
+            .memory
+            .map(|memory| memory.to_string().to_lowercase()),
+        cpus: spec.limits.cpus,
+        stdin_open: spec.stdin,
+        tty: spec.tty,
+        healthcheck: spec.health_check.as_ref().map(|health_check| Healthcheck {
+            test: health_check.test.clone().unwrap_or_default(),
+            interval: health_check.interval.filter(|i| *i > 0).map(duration),
+            timeout: health_check.timeout.filter(|t| *t > 0).map(duration),
+            retries: health_check.retries.filter(|r| *r > 0),
+            start_period: health_check.start_period.filter(|s| *s > 0).map(duration),
+        }),
+    };
+    let mut services = BTreeMap::new();
+    services.insert(spec.name.clone(), service);
+    Ok(serde_yaml::to_string(&Compose { services, volumes })?)
+}
@@ -0,0 +1,305 @@
+use anyhow::Result;
+use bollard::models::PortTypeEnum;
+use clap::ArgEnum;
+use docker_game_servers::PortConfiguration;
+use serde_json::{json, Value};
+
+use super::{ServerSpec, VolumeSource};
+
+/// Size requested for every volume, as the engine does not limit them
+const STORAGE: &str = "10Gi";
+
+/// Node ports Kubernetes accepts by default
+const NODE_PORTS: std::ops::RangeInclusive<u16> = 30000..=32767;
+
+/// How the ports of the server are reachable from outside the cluster
+#[derive(Clone, Copy, ArgEnum)]
+pub enum ServiceType {
+    /// On every node, on the host port of the server if it is a valid node port
+    NodePort,
+    /// On an address of the load balancer, on the host port of the server
+    LoadBalancer,
+}
+
+/// A `StatefulSet` with one `PersistentVolumeClaim` per volume and a `Service`
+/// publishing the ports of the game, credentials are stored in a `Secret`
+pub fn k8s(spec: &ServerSpec, service_type: ServiceType) -> Result<String> {
+    let name = dns_name(&spec.name);
+    let selector = json!({ "app.kubernetes.io/name": name });
+    let labels = json!({
+        "app.kubernetes.io/name": name,
+        "app.kubernetes.io/component": &*spec.game.name,
+        "app.kubernetes.io/managed-by": "dgs",
+    });
+
+    let claims: Vec<(String, &str, bool)> = spec
+        .volumes
+        .iter()
+        .map(|volume| {
+            let claim = match &volume.source {
+                VolumeSource::Named(volume) => dns_name(volume),
+                // Paths on the host do not exist in the cluster
+                VolumeSource::Bind(path) => {
+                    eprintln!(
+                        "Warning: `{}` is bound from the host, it is exported as an empty volume",
+                        path
+                    );
+                    dns_name(&format!("{}-{}", spec.name, volume.target))
+                }
+            };
+            (claim, volume.target.as_str(), volume.read_only)
+        })
+        .collect();
+
+    if let (PortConfiguration::NonConfigurable(_), ServiceType::NodePort) =
+        (&spec.game.ports, service_type)
+    {
+        eprintln!(
+            "Warning: {} announces its ports to clients, which only works with `--service-type load-balancer`",
+            spec.game.name
+        );
+    }
+    let ports: Vec<(u16, PortTypeEnum, u16)> = spec
+        .game
+        .ports
+        .ports()
+        .iter()
+        .map(|&(container, typ)| {
+            let host = spec
+                .ports
+                .iter()
+                .find(|port| port.container == container && port.typ == typ)
+                .map(|port| port.host)
+                .unwrap_or(container);
+            (container, typ, host)
+        })
+        .collect();
+
+    // Credentials e.g. the RCON password are only readable with access to the secret
+    let secrets: serde_json::Map<String, Value> = spec
+        .secrets
+        .iter()
+        .map(|env| {
+            let (key, value) = env.split_once('=').unwrap_or((env, ""));
+            (key.to_string(), json!(value))
+        })
+        .collect();
+    let env: Vec<_> = spec
+        .env
+        .iter()
+        .map(|env| {
+            let (key, value) = env.split_once('=').unwrap_or((env, ""));
+            json!({ "name": key, "value": value })
+        })
+        .chain(secrets.keys().map(|key| {
+            json!({
+                "name": key,
+                "valueFrom": { "secretKeyRef": { "name": name, "key": key } },
+            })
+        }))
+        .collect();
+    let container_ports: Vec<_> = ports
+        .iter()
+        .map(|&(container, typ, _)| {
+            json!({
+                "name": port_name(container, typ),
+                "containerPort": container,
+                "protocol": protocol(typ),
+            })
+        })
+        .collect();
+    let mounts: Vec<_> = claims
+        .iter()
+        .map(|(claim, target, read_only)| {
+            json!({ "name": claim, "mountPath": target, "readOnly": read_only })
+        })
+        .collect();
+    let mut container = json!({
+        "name": name,
+        "image": spec.image,
+        "env": env,
+        "ports": container_ports,
+        "volumeMounts": mounts,
+        "stdin": spec.stdin,
+        "tty": spec.tty,
+    });
+    if let Some(entrypoint) = &spec.entrypoint {
+        container["command"] = json!(entrypoint);
+    }
+    if let Some(cmd) = &spec.cmd {
+        container["args"] = json!(cmd);
+    }
+    let mut limits = json!({});
+    if let Some(memory) = spec.limits.memory {
+        // `2G` in dgs are two GiB
+        let memory = memory.to_string();
+        limits["memory"] = json!(if memory.ends_with(char::is_alphabetic) {
+            memory + "i"
+        } else {
+            memory
+        });
+    }
+    if let Some(cpus) = spec.limits.cpus {
+        limits["cpu"] = json!(cpus.to_string());
+    }
+    if limits
+        .as_object()
+        .map_or(false, |limits| !limits.is_empty())
+    {
+        container["resources"] = json!({ "limits": limits });
+    }
+    if let Some(health_check) = &spec.health_check {
+        let command = match health_check.test.as_deref() {
+            Some([kind, command]) if kind == "CMD-SHELL" => {
+                Some(vec!["sh".to_string(), "-c".into(), command.clone()])
+            }
+            Some([kind, command @ ..]) if kind == "CMD" && !command.is_empty() => {
+                Some(command.to_vec())
+            }
+            _ => None,
+        };
+        if let Some(command) = command {
+            let seconds = |nanoseconds: Option<i64>| {
+                nanoseconds
+                    .filter(|nanoseconds| *nanoseconds > 0)
+                    .map(|nanoseconds| (nanoseconds / 1_000_000_000).max(1))
+            };
+            let mut probe = json!({ "exec": { "command": command } });
+            if let Some(interval) = seconds(health_check.interval) {
+                probe["periodSeconds"] = json!(interval);
+            }
+            if let Some(timeout) = seconds(health_check.timeout) {
+                probe["timeoutSeconds"] = json!(timeout);
+            }
+            if let Some(retries) = health_check.retries.filter(|r| *r > 0) {
+                probe["failureThreshold"] = json!(retries);
+            }
+            if let Some(start_period) = seconds(health_check.start_period) {
+                probe["initialDelaySeconds"] = json!(start_period);
+            }
+            container["readinessProbe"] = probe;
+        }
+    }
+
+    let volumes: Vec<_> = claims
+        .iter()
+        .map(
+            |(claim, ..)| json!({ "name": claim, "persistentVolumeClaim": { "claimName": claim } }),
+        )
+        .collect();
+    let stateful_set = json!({
+        "apiVersion": "apps/v1",
+        "kind": "StatefulSet",
+        "metadata": { "name": name, "labels": labels },
+        "spec": {
+            "serviceName": name,
+            "replicas": 1,
+            "selector": { "matchLabels": selector },
+            "template": {
+                "metadata": {
+                    "labels": labels,
+                    // Label values of dgs are not always valid Kubernetes labels
+                    "annotations": spec.labels,
+                },
+                "spec": {
+                    "containers": [container],
+                    "volumes": volumes,
+                },
+            },
+        },
+    });
+    let service_ports: Vec<_> = ports
+        .iter()
+        .map(|&(container, typ, host)| {
+            let mut port = json!({
+                "name": port_name(container, typ),
+                "port": container,
+                "targetPort": container,
+                "protocol": protocol(typ),
+            });
+            match service_type {
+                ServiceType::NodePort if NODE_PORTS.contains(&host) => {
+                    port["nodePort"] = json!(host);
+                }
+                ServiceType::NodePort => eprintln!(
+                    "Warning: Port {} is not a valid node port ({}-{}), Kubernetes assigns a random one to {}/{}, see `kubectl get service {}`",
+                    host,
+                    NODE_PORTS.start(),
+                    NODE_PORTS.end(),
+                    container,
+                    typ,
+                    name
+                ),
+                ServiceType::LoadBalancer => port["port"] = json!(host),
+            }
+            port
+        })
+        .collect();
+    let service = json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": { "name": name, "labels": labels },
+        "spec": {
+            "type": match service_type {
+                ServiceType::NodePort => "NodePort",
+                ServiceType::LoadBalancer => "LoadBalancer",
+            },
+            "selector": selector,
+            "ports": service_ports,
+        },
+    });
+
+    let mut documents: Vec<Value> = Vec::new();
+    if !secrets.is_empty() {
+        documents.push(json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": { "name": name, "labels": labels },
+            "type": "Opaque",
+            "stringData": secrets,
+        }));
+    }
+    documents.extend(claims.iter().map(|(claim, ..)| {
+        json!({
+            "apiVersion": "v1",
+            "kind": "PersistentVolumeClaim",
+            "metadata": { "name": claim, "labels": labels },
+            "spec": {
+                "accessModes": ["ReadWriteOnce"],
+                "resources": { "requests": { "storage": STORAGE } },
+            },
+        })
+    }));
+    documents.push(stateful_set);
+    documents.push(service);
+    // Every document starts with `---`
+    documents
+        .iter()
+        .map(|document| Ok(serde_yaml::to_string(document)?))
+        .collect()
+}
+
+/// Kubernetes names only allow lowercase letters, digits and `-`
+fn dns_name(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .intersperse("-")
+        .collect()
+}
+
+/// Ports need a unique name once there is more than one e.g. `udp-2456`
+fn port_name(port: u16, typ: PortTypeEnum) -> String {
+    format!("{}-{}", typ, port)
+}
+
+fn protocol(typ: PortTypeEnum) -> &'static str {
+    match typ {
+        PortTypeEnum::UDP => "UDP",
+        PortTypeEnum::SCTP => "SCTP",
+        _ => "TCP",
+    }
+}
@@ -0,0 +1,502 @@
+//! Describing a server in the formats of other tools, so it can run without dgs
+mod compose;
+mod k8s;
+mod systemd;
+
+use std::collections::BTreeMap;
+use std::env::current_dir;
+use std::fmt::{self, Display};
+use std::fs::{create_dir_all, set_permissions, write, OpenOptions, Permissions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use bollard::models::{ContainerConfig, HealthConfig, PortTypeEnum};
+use clap::{ArgEnum, Clap};
+use docker_game_servers::engine::Engine;
+use docker_game_servers::manifest::MANIFEST_LABEL;
+use docker_game_servers::resources::Limits;
+use docker_game_servers::{Game, ServerManager, RCON_PASSWORD_LABEL};
+
+use self::k8s::ServiceType;
+use crate::host::{Host, Runtime};
+
+/// Envs of games holding credentials, besides the ones setting the RCON password
+const SECRET_ENVS: &[&str] = &["SERVER_PASS", "CF_API_KEY", "TOKEN"];
+
+#[derive(Clap)]
+pub struct Export {
+    /// Name of the server
+    name: String,
+    /// What to export the server as
+    #[clap(short, long, arg_enum, default_value = "compose")]
+    format: Format,
+    /// Reference the image by its digest instead of its tag
+    ///
+    /// Guarantees the exact same version of the server software is used.
+    #[clap(long)]
+    digest: bool,
+    /// Write the unit to the directory systemd loads it from instead of printing it
+    ///
+    /// User units are used with `--podman-user`, system units otherwise.
+    #[clap(long)]
+    install: bool,
+    /// How the ports are reachable from outside the cluster with `--format k8s`
+    #[clap(long, arg_enum, default_value = "node-port")]
+    service_type: ServiceType,
+}
+
+impl Export {
+    pub fn validate(&self) -> Result<()> {
+        if self.install && matches!(self.format, Format::Compose | Format::K8s) {
+            bail!("`--install` is only supported for `--format systemd` and `--format quadlet`");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, ArgEnum)]
+pub enum Format {
+    /// A `docker-compose.yml`
+    Compose,
+    /// A systemd `.service` running the server with Docker or Podman
+    Systemd,
+    /// A Podman Quadlet `.container`
+    Quadlet,
+    /// Kubernetes manifests for a `StatefulSet` with its volumes and a `Service`
+    K8s,
+}
+
+/// A port published on the host
+struct PublishedPort {
+    /// Only set if the port is not published on every interface e.g. `127.0.0.1`
+    host_ip: Option<String>,
+    host: u16,
+    container: u16,
+    typ: PortTypeEnum,
+}
+
+impl Display for PublishedPort {
+    /// In the `docker run -p` syntax e.g. `2456:2456/udp`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(host_ip) = &self.host_ip {
+            write!(f, "{}:", host_ip)?;
+        }
+        write!(f, "{}:{}", self.host, self.container)?;
+        match self.typ {
+            PortTypeEnum::TCP => Ok(()),
+            typ => write!(f, "/{}", typ),
+        }
+    }
+}
+
+enum VolumeSource {
+    /// A volume managed by the engine
+    Named(String),
+    /// A path on the host
+    Bind(String),
+}
+
+struct Volume {
+    source: VolumeSource,
+    target: String,
+    read_only: bool,
+}
+
+impl Display for Volume {
+    /// In the `docker run -v` syntax e.g. `survival:/data:ro`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match &self.source {
+            VolumeSource::Named(name) => name,
+            VolumeSource::Bind(path) => path,
+        };
+        write!(f, "{}:{}", source, self.target)?;
+        if self.read_only {
+            write!(f, ":ro")?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything about a server needed to recreate it, without what the image
+/// sets itself
+struct ServerSpec {
+    /// Without the leading `/`
+    name: String,
+    game: &'static Game,
+    image: String,
+    entrypoint: Option<Vec<String>>,
+    cmd: Option<Vec<String>>,
+    env: Vec<String>,
+    /// Envs with credentials e.g. the RCON password, kept out of the exported file
+    secrets: Vec<String>,
+    ports: Vec<PublishedPort>,
+    volumes: Vec<Volume>,
+    /// Without the labels holding the RCON password and the manifest entry
+    labels: BTreeMap<String, String>,
+    limits: Limits,
+    stdin: bool,
+    tty: bool,
+    health_check: Option<HealthConfig>,
+}
+
+impl ServerSpec {
+    async fn inspect<E: Engine>(
+        manager: &ServerManager<E>,
+        name: &str,
+        digest: bool,
+    ) -> Result<Self> {
+        let server = manager.find(name).await?;
+        let container = manager.inspect(&server.id).await?;
+        let config = container.config.unwrap_or_default();
+        let defaults = manager.image_config(&server.id).await?;
+        let image_name = if digest {
+            manager.image_digest(&server.id).await?
+        } else {
+            config.image.clone().unwrap_or_default()
+        };
+        let host_config = container.host_config.unwrap_or_default();
+
+        let mut ports: Vec<_> = host_config
+            .port_bindings
+            .clone()
+            .into_iter()
+            .flatten()
+            .filter_map(|(port, bindings)| {
+                let (container, typ) = port.split_once('/')?;
+                let (container, typ) = (container.parse().ok()?, typ.parse().ok()?);
+                Some(bindings.into_iter().flatten().filter_map(move |binding| {
+                    Some(PublishedPort {
+                        host_ip: binding.host_ip.filter(|ip| !ip.is_empty()),
+                        host: binding.host_port?.parse().ok()?,
+                        container,
+                        typ,
+                    })
+                }))
+            })
+            .flatten()
+            .collect();
+        ports.sort_by_key(|port| (port.container, port.typ, port.host));
+
+        let server_name = server.name.trim_start_matches('/').to_string();
+        let mut volumes: Vec<_> = container
+            .mounts
+            .into_iter()
+            .flatten()
+            .filter_map(|mount| {
+                let target = mount.destination?;
+                let source = match mount.typ.as_deref() {
+                    Some("volume") => {
+                        let name = mount.name?;
+                        // Anonymous volumes are named after their target
+                        VolumeSource::Named(if is_anonymous(&name) {
+                            format!(
+                                "{}-{}",
+                                server_name,
+                                target.trim_matches('/').replace('/', "-")
+                            )
+                        } else {
+                            name
+                        })
+                    }
+                    Some("bind") => VolumeSource::Bind(mount.source?),
+                    _ => return None,
+                };
+                Some(Volume {
+                    source,
+                    target,
+                    read_only: mount.rw == Some(false),
+                })
+            })
+            .collect();
+        volumes.sort_by(|a, b| a.target.cmp(&b.target));
+
+        let (secrets, env) = without_defaults(config.env.clone(), defaults.env.clone())
+            .into_iter()
+            .partition(|env| is_secret(server.game, env));
+
+        Ok(Self {
+            name: server_name,
+            game: server.game,
+            image: image_name,
+            entrypoint: config
+                .entrypoint
+                .clone()
+                .filter(|entrypoint| Some(entrypoint) != defaults.entrypoint.as_ref()),
+            cmd: config
+                .cmd
+                .clone()
+                .filter(|cmd| Some(cmd) != defaults.cmd.as_ref()),
+            env,
+            secrets,
+            ports,
+            volumes,
+            labels: config
+                .labels
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(key, value)| {
+                    key != RCON_PASSWORD_LABEL
+                        && key != MANIFEST_LABEL
+                        && defaults.labels.as_ref().and_then(|labels| labels.get(key))
+                            != Some(value)
+                })
+                .collect(),
+            limits: Limits::from_host_config(host_config),
+            stdin: config.open_stdin.unwrap_or_default(),
+            tty: config.tty.unwrap_or_default(),
+            health_check: health_check(&config, &defaults),
+        })
+    }
+}
+
+/// Anonymous volumes get a random id as name
+fn is_anonymous(name: &str) -> bool {
+    name.len() == 64 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Whether `env` holds credentials of `game`
+fn is_secret(game: &Game, env: &str) -> bool {
+    let key = env.split_once('=').map_or(env, |(key, _)| key);
+    SECRET_ENVS.contains(&key)
+        || game
+            .rcon
+            .iter()
+            .flat_map(|rcon| rcon.envs)
+            .any(|rcon_env| rcon_env.split_once('=') == Some((key, "{password}")))
+}
+
+/// Writes the secret envs of `spec` in the `--env-file` syntax to a file only
+/// the current user can read
+fn write_env_file(path: &Path, spec: &ServerSpec) -> Result<()> {
+    let context = || format!("Unable to write `{}`", path.display());
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(context)?;
+    // The mode is only used for new files
+    set_permissions(path, Permissions::from_mode(0o600)).with_context(context)?;
+    for secret in &spec.secrets {
+        writeln!(file, "{}", secret).with_context(context)?;
+    }
+    eprintln!(
+        "Wrote the credentials of `{}` to `{}`, keep it next to the export and out of version control",
+        spec.name,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Drops every entry that is the same in `defaults`
+fn without_defaults(values: Option<Vec<String>>, defaults: Option<Vec<String>>) -> Vec<String> {
+    let defaults = defaults.unwrap_or_default();
+    values
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|value| !defaults.contains(value))
+        .collect()
+}
+
+/// The health check of the container, unless it is the one of the image
+fn health_check(config: &ContainerConfig, defaults: &ContainerConfig) -> Option<HealthConfig> {
+    config
+        .healthcheck
+        .clone()
+        .filter(|health_check| Some(health_check) != defaults.healthcheck.as_ref())
+        .filter(
+            |health_check| !matches!(health_check.test.as_deref(), Some([test]) if test == "NONE"),
+        )
+}
+
+/// A duration from the engine in nanoseconds e.g. `10s`
+fn duration(nanoseconds: i64) -> String {
+    if nanoseconds % 1_000_000_000 == 0 {
+        format!("{}s", nanoseconds / 1_000_000_000)
+    } else {
+        format!("{}ms", nanoseconds / 1_000_000)
+    }
+}
+
+/// The engine of `host`, hosts from the config are asked for it
+async fn runtime(host: &Host) -> Result<Runtime> {
+    if let Some(runtime) = host.runtime {
+        return Ok(runtime);
+    }
+    let podman = host
+        .manager
+        .engine_version()
+        .await?
+        .components
+        .iter()
+        .any(|component| component.starts_with("Podman"));
+    // Rootless Podman cannot be told apart over the API, so system units are generated
+    Ok(if podman {
+        Runtime::PodmanSystem
+    } else {
+        Runtime::Docker
+    })
+}
+
+/// Where systemd picks up units of `format` for `runtime`
+fn unit_dir(format: Format, runtime: Runtime) -> Result<PathBuf> {
+    let config_dir =
+        || dirs::config_dir().expect("There should be a config dir ($XDG_CONFIG_HOME)");
+    Ok(match (format, runtime) {
+        (Format::Systemd, Runtime::PodmanUser) => config_dir().join("systemd/user"),
+        (Format::Systemd, _) => PathBuf::from("/etc/systemd/system"),
+        (Format::Quadlet, Runtime::PodmanUser) => config_dir().join("containers/systemd"),
+        (Format::Quadlet, Runtime::PodmanSystem) => PathBuf::from("/etc/containers/systemd"),
+        (Format::Quadlet, Runtime::Docker) => {
+            bail!("Quadlet is part of Podman, use `--podman-user` or `--podman-system`")
+        }
+        (Format::Compose, _) | (Format::K8s, _) => {
+            unreachable!("Rejected by `Export::validate`")
+        }
+    })
+}
+
+pub async fn export(
+    Export {
+        name,
+        format,
+        digest,
+        install,
+        service_type,
+    }: Export,
+    host: &Host,
+) -> Result<()> {
+    let spec = ServerSpec::inspect(&host.manager, &name, digest).await?;
+    let installed = if install {
+        let runtime = match host.runtime {
+            Some(runtime) => runtime,
+            None => bail!("Units can only be installed for the local engine, not with `--host`"),
+        };
+        let dir = unit_dir(format, runtime)?;
+        create_dir_all(&dir).with_context(|| format!("Unable to create `{}`", dir.display()))?;
+        Some((runtime, dir))
+    } else {
+        None
+    };
+    let env_name = format!("{}.env", spec.name);
+    let env_file = if spec.secrets.is_empty() || matches!(format, Format::K8s) {
+        None
+    } else {
+        let dir = match &installed {
+            Some((_, dir)) => dir.clone(),
+            None => current_dir()?,
+        };
+        let path = dir.join(&env_name);
+        write_env_file(&path, &spec)?;
+        Some(path)
+    };
+    let (content, extension) = match format {
+        Format::Compose => (
+            compose::compose(&spec, env_file.as_ref().map(|_| env_name.as_str()))?,
+            "yml",
+        ),
+        Format::Systemd => (
+            systemd::systemd(&spec, runtime(host).await?, env_file.as_deref())?,
+            "service",
+        ),
+        Format::Quadlet => (
+            systemd::quadlet(&spec, runtime(host).await?, env_file.as_deref())?,
+            "container",
+        ),
+        Format::K8s => (k8s::k8s(&spec, service_type)?, "yaml"),
+    };
+    let (runtime, dir) = match installed {
+        Some(installed) => installed,
+        None => {
+            print!("{}", content);
+            return Ok(());
+        }
+    };
+    let path = dir.join(format!("{}.{}", spec.name, extension));
+    write(&path, content).with_context(|| format!("Unable to write `{}`", path.display()))?;
+    println!("Installed `{}`", path.display());
+
+    let systemctl = if runtime == Runtime::PodmanUser {
+        "systemctl --user"
+    } else {
+        "systemctl"
+    };
+    // The unit replaces the container, so dgs should not start it anymore
+    println!(
+        "Stop the server with dgs, then run `{0} daemon-reload` and `{0} {1} {2}.service`",
+        systemctl,
+        // Units generated from Quadlet files are enabled by their `[Install]` section
+        if matches!(format, Format::Quadlet) {
+            "start"
+        } else {
+            "enable --now"
+        },
+        spec.name
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use docker_game_servers::engine::fake::FakeEngine;
+    use docker_game_servers::{GameOptions, ServerSetup};
+    use futures_util::TryStreamExt;
+
+    use super::*;
+
+    async fn minecraft() -> ServerManager<FakeEngine> {
+        let game = Game::find_by_name("minecraft").unwrap();
+        let manager = ServerManager::new(FakeEngine::default());
+        manager
+            .pull(game, None)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let options = GameOptions {
+            accept_eula: true,
+            ..Default::default()
+        };
+        let setup = ServerSetup {
+            name: Some("survival".into()),
+            volumes: vec!["survival:/data".into()],
+            ..Default::default()
+        };
+        manager.create_with(game, options, setup).await.unwrap();
+        manager
+    }
+
+    #[tokio::test]
+    async fn exports_without_credentials() {
+        let manager = minecraft().await;
+        let password = manager.rcon_password("survival").await.unwrap().unwrap();
+        let spec = ServerSpec::inspect(&manager, "survival", false)
+            .await
+            .unwrap();
+        assert_eq!(spec.secrets, vec![format!("RCON_PASSWORD={}", password)]);
+
+        let compose = compose::compose(&spec, Some("survival.env")).unwrap();
+        assert!(compose.contains("image: itzg/minecraft-server"));
+        assert!(compose.contains("survival:/data"));
+        assert!(compose.contains("survival.env"));
+        assert!(!compose.contains(&password));
+        assert!(!compose.contains(RCON_PASSWORD_LABEL));
+
+        let k8s = k8s::k8s(&spec, ServiceType::LoadBalancer).unwrap();
+        assert!(k8s.contains("kind: Secret"));
+        assert!(k8s.contains("secretKeyRef"));
+        assert_eq!(k8s.matches(&password).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn exports_digest_of_pulled_image() {
+        let manager = minecraft().await;
+        let spec = ServerSpec::inspect(&manager, "survival", true)
+            .await
+            .unwrap();
+        assert!(spec.image.starts_with("itzg/minecraft-server@sha256:"));
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use bollard::models::HealthConfig;
+use docker_game_servers::resources::RestartPolicy;
+
+use super::{duration, ServerSpec};
+use crate::host::Runtime;
+
+/// A `.service` running the server with `docker run` or `podman run`
+///
+/// The secret envs are read from `env_file`.
+pub fn systemd(spec: &ServerSpec, runtime: Runtime, env_file: Option<&Path>) -> Result<String> {
+    let podman = runtime != Runtime::Docker;
+    let engine = if podman { "podman" } else { "docker" };
+    let mut args = vec!["run".to_string(), "--rm".into()];
+    if podman {
+        // Podman runs the server detached and notifies systemd once it started
+        args.extend(vec![
+            "--replace".into(),
+            "--sdnotify=conmon".into(),
+            "--detach".into(),
+        ]);
+    }
+    args.extend(vec!["--name".into(), spec.name.clone()]);
+    let (entrypoint, cmd) = entrypoint(spec);
+    if let Some(entrypoint) = entrypoint {
+        args.extend(vec!["--entrypoint".into(), entrypoint]);
+    }
+    for env in &spec.env {
+        args.extend(vec!["--env".into(), env.clone()]);
+    }
+    if let Some(env_file) = env_file {
+        args.extend(vec!["--env-file".into(), env_file.display().to_string()]);
+    }
+    for port in &spec.ports {
+        args.extend(vec!["--publish".into(), port.to_string()]);
+    }
+    for volume in &spec.volumes {
+        args.extend(vec!["--volume".into(), volume.to_string()]);
+    }
+    for (key, value) in &spec.labels {
+        args.extend(vec!["--label".into(), format!("{}={}", key, value)]);
+    }
+    if let Some(memory) = spec.limits.memory {
+        args.extend(vec!["--memory".into(), memory.to_string().to_lowercase()]);
+    }
+    if let Some(cpus) = spec.limits.cpus {
+        args.extend(vec!["--cpus".into(), cpus.to_string()]);
+    }
+    if spec.stdin {
+        args.push("--interactive".into());
+    }
+    // The docker client refuses to allocate a TTY without a terminal
+    if spec.tty && podman {
+        args.push("--tty".into());
+    }
+    if let Some(health_check) = &spec.health_check {
+        args.extend(health_args(health_check));
+    }
+    args.push(spec.image.clone());
+    args.extend(cmd);
+
+    let mut unit = String::new();
+    writeln!(unit, "[Unit]")?;
+    writeln!(unit, "Description=dgs server {}", spec.name)?;
+    writeln!(unit, "Wants=network-online.target")?;
+    if podman {
+        writeln!(unit, "After=network-online.target")?;
+    } else {
+        writeln!(unit, "After=network-online.target docker.service")?;
+        writeln!(unit, "Requires=docker.service")?;
+    }
+    writeln!(unit)?;
+    writeln!(unit, "[Service]")?;
+    if podman {
+        writeln!(unit, "Type=notify")?;
+        writeln!(unit, "NotifyAccess=all")?;
+    } else {
+        writeln!(
+            unit,
+            "ExecStartPre=-docker rm --force {}",
+            quote(&spec.name)
+        )?;
+    }
+    writeln!(unit, "Restart={}", restart(spec))?;
+    writeln!(unit, "ExecStart={} {}", engine, join(&args))?;
+    writeln!(unit, "ExecStop={} stop {}", engine, quote(&spec.name))?;
+    if podman {
+        writeln!(
+            unit,
+            "ExecStopPost=podman rm --force --ignore {}",
+            quote(&spec.name)
+        )?;
+    }
+    writeln!(unit)?;
+    writeln!(unit, "[Install]")?;
+    writeln!(unit, "WantedBy={}", wanted_by(runtime))?;
+    Ok(unit)
+}
+
+/// A Podman Quadlet `.container`, which systemd turns into a `.service`
+///
+/// The secret envs are read from `env_file`.
+pub fn quadlet(spec: &ServerSpec, runtime: Runtime, env_file: Option<&Path>) -> Result<String> {
+    let mut unit = String::new();
+    writeln!(unit, "[Unit]")?;
+    writeln!(unit, "Description=dgs server {}", spec.name)?;
+    writeln!(unit, "Wants=network-online.target")?;
+    writeln!(unit, "After=network-online.target")?;
+    writeln!(unit)?;
+    writeln!(unit, "[Container]")?;
+    writeln!(unit, "ContainerName={}", escape(&spec.name))?;
+    writeln!(unit, "Image={}", escape(&spec.image))?;
+    let (entrypoint, cmd) = entrypoint(spec);
+    if let Some(entrypoint) = entrypoint {
+        writeln!(unit, "Entrypoint={}", escape(&entrypoint))?;
+    }
+    if !cmd.is_empty() {
+        writeln!(unit, "Exec={}", join(&cmd))?;
+    }
+    for env in &spec.env {
+        writeln!(unit, "Environment={}", quote(env))?;
+    }
+    if let Some(env_file) = env_file {
+        writeln!(
+            unit,
+            "EnvironmentFile={}",
+            escape(&env_file.display().to_string())
+        )?;
+    }
+    for port in &spec.ports {
+        writeln!(unit, "PublishPort={}", port.to_string())?;
+    }
+    for volume in &spec.volumes {
+        writeln!(unit, "Volume={}", escape(&volume.to_string()))?;
+    }
+    for (key, value) in &spec.labels {
+        writeln!(unit, "Label={}", quote(&format!("{}={}", key, value)))?;
+    }
+    let mut podman_args = Vec::new();
+    if let Some(memory) = spec.limits.memory {
+        podman_args.push(format!("--memory={}", memory.to_string().to_lowercase()));
+    }
+    if let Some(cpus) = spec.limits.cpus {
+        podman_args.push(format!("--cpus={}", cpus));
+    }
+    if spec.stdin {
+        podman_args.push("--interactive".into());
+    }
+    if spec.tty {
+        podman_args.push("--tty".into());
+    }
+    if !podman_args.is_empty() {
+        writeln!(unit, "PodmanArgs={}", join(&podman_args))?;
+    }
+    if let Some(health_check) = &spec.health_check {
+        let test = health_check.test.clone().unwrap_or_default();
+        if let Some(command) = health_command(&test) {
+            writeln!(unit, "HealthCmd={}", escape(&command))?;
+        }
+        if let Some(interval) = health_check.interval.filter(|i| *i > 0) {
+            writeln!(unit, "HealthInterval={}", duration(interval))?;
+        }
+        if let Some(timeout) = health_check.timeout.filter(|t| *t > 0) {
+            writeln!(unit, "HealthTimeout={}", duration(timeout))?;
+        }
+        if let Some(retries) = health_check.retries.filter(|r| *r > 0) {
+            writeln!(unit, "HealthRetries={}", retries)?;
+        }
+        if let Some(start_period) = health_check.start_period.filter(|s| *s > 0) {
+            writeln!(unit, "HealthStartPeriod={}", duration(start_period))?;
+        }
+    }
+    writeln!(unit)?;
+    writeln!(unit, "[Service]")?;
+    writeln!(unit, "Restart={}", restart(spec))?;
+    writeln!(unit)?;
+    writeln!(unit, "[Install]")?;
+    writeln!(unit, "WantedBy={}", wanted_by(runtime))?;
+    Ok(unit)
+}
+
+/// The engines only take a single entrypoint, the rest is passed before the command
+fn entrypoint(spec: &ServerSpec) -> (Option<String>, Vec<String>) {
+    let mut entrypoint = spec.entrypoint.clone().unwrap_or_default().into_iter();
+    let first = entrypoint.next();
+    (
+        first,
+        entrypoint
+            .chain(spec.cmd.clone().into_iter().flatten())
+            .collect(),
+    )
+}
+
+fn health_args(health_check: &HealthConfig) -> Vec<String> {
+    let test = health_check.test.clone().unwrap_or_default();
+    let mut args = Vec::new();
+    if let Some(command) = health_command(&test) {
+        args.extend(vec!["--health-cmd".into(), command]);
+    }
+    if let Some(interval) = health_check.interval.filter(|i| *i > 0) {
+        args.extend(vec!["--health-interval".into(), duration(interval)]);
+    }
+    if let Some(timeout) = health_check.timeout.filter(|t| *t > 0) {
+        args.extend(vec!["--health-timeout".into(), duration(timeout)]);
+    }
+    if let Some(retries) = health_check.retries.filter(|r| *r > 0) {
+        args.extend(vec!["--health-retries".into(), retries.to_string()]);
+    }
+    if let Some(start_period) = health_check.start_period.filter(|s| *s > 0) {
+        args.extend(vec!["--health-start-period".into(), duration(start_period)]);
+    }
+    args
+}
+
+/// The test of a health check as a single shell command
+fn health_command(test: &[String]) -> Option<String> {
+    match test {
+        [kind, command] if kind == "CMD-SHELL" => Some(command.clone()),
+        [kind, command @ ..] if kind == "CMD" && !command.is_empty() => Some(command.join(" ")),
+        _ => None,
+    }
+}
+
+/// Restarting is left to systemd instead of the engine
+fn restart(spec: &ServerSpec) -> &'static str {
+    match spec.limits.restart {
+        Some(RestartPolicy::No) => "no",
+        Some(RestartPolicy::Always) | Some(RestartPolicy::UnlessStopped) => "always",
+        Some(RestartPolicy::OnFailure) | None => "on-failure",
+    }
+}
+
+fn wanted_by(runtime: Runtime) -> &'static str {
+    match runtime {
+        Runtime::PodmanUser => "default.target",
+        _ => "multi-user.target",
+    }
+}
+
+fn join(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote(arg))
+        .intersperse(" ".into())
+        .collect()
+}
+
+/// Quotes `arg` if systemd would otherwise split it
+fn quote(arg: &str) -> String {
+    let escaped = escape(arg);
+    if arg.is_empty()
+        || arg
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+/// Escapes specifiers and variables, which systemd would expand
+fn escape(value: &str) -> String {
+    value.replace('%', "%%").replace('$', "$$")
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use bollard::models::ContainerStateStatusEnum;
+use clap::Clap;
+use docker_game_servers::query::query;
+use docker_game_servers::{BasicServerInfo, Game, Port};
+
+use crate::host::Host;
+
+#[derive(Clap)]
+pub struct Info {
+    /// Name of the server
+    name: String,
+}
+
+pub async fn info(Info { name }: Info, host: &Host) -> Result<()> {
+    let server = host.manager.find(&name).await?;
+    let limits = host.manager.limits(&server.id).await?;
+    let status = if server.status == ContainerStateStatusEnum::RUNNING {
+        query(&server, &host.address).await
+    } else {
+        None
+    };
+
+    let BasicServerInfo {
+        name,
+        game: Game {
+            name: game_name, ..
+        },
+        tags,
+        ports,
+        status: state,
+        health,
+        server_type,
+        ..
+    } = server;
+    println!("name: {}", name);
+    println!("game: {}", game_name);
+    if let Some(server_type) = server_type {
+        println!("type: {}", server_type);
+    }
+    println!("status: {:?}", state);
+    if let Some(health) = health {
+        println!("health: {}", health);
+    }
+    if !tags.is_empty() {
+        println!("tags: {}", tags.join(", "));
+    }
+    for Port {
+        public,
+        private,
+        typ,
+    } in ports
+    {
+        println!("port: {}:{}->{}", typ, public, private);
+    }
+    print!("{}", limits);
+    match status {
+        Some(Ok(status)) => print!("{}", status),
+        Some(Err(e)) => println!("query failed: {}", e),
+        None => {}
+    }
+    Ok(())
+}
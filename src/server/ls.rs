@@ -1,150 +1,295 @@
-use std::collections::HashMap;
-use std::convert::TryFrom;
-use std::iter;
+use std::time::Duration;
 
-use anyhow::{bail, Result};
-use bollard::container::ListContainersOptions;
+use anyhow::{anyhow, Result};
 use bollard::models::{ContainerStateStatusEnum, PortTypeEnum};
-use bollard::Docker;
 use clap::Clap;
-use comfy_table::presets::UTF8_FULL;
-use comfy_table::{Cell, CellAlignment, ContentArrangement, Table};
+use comfy_table::Cell;
+use docker_game_servers::query::{query, ServerStatus};
+use docker_game_servers::resources::{Limits, Usage};
+use docker_game_servers::{BasicServerInfo, Error, Game, InvalidServer, Port, ServerFilter};
+use futures_util::future::join_all;
+use futures_util::stream::{select_all, StreamExt};
+use termion::{clear, cursor};
+use tokio::time::timeout;
 
-use crate::cli::LowerCaseString;
-use crate::{BasicServerInfo, GAMES, Game, GameName, Port, UTF8_SOLID_INNER_BORDERS};
+use super::stats::{usage_cells, USAGE_HEADER};
+use super::table;
+use crate::host::Host;
+
+/// What `dgs server ls` shows
 #[derive(Clap, Default)]
-pub struct ServerFilter {
-    /// Only servers matching the name will be returned.
+pub struct ListOptions {
+    #[clap(flatten)]
+    pub filter: ServerFilter,
+    /// Query every configured host instead of only the selected one
     #[clap(short, long)]
-    pub name: Option<String>,
-    /// Only servers with a matching game name will be returned.
-    #[clap(short, long, arg_enum)]
-    pub game: Option<GameName>,
-    /// Only servers with these tags (case is ignored) will be returned.
+    pub all_hosts: bool,
+    /// Show more details e.g. the configured resource limits and online players
     ///
-    /// Usage: `-t first_tag -t second_tag`.
-    /// This would return all servers that have both `first_tag` and `second_tag`.
-    #[clap(short, long = "tag")]
-    pub tags: Vec<LowerCaseString>,
-    /// Only servers with this state are returned
+    /// This needs additional requests for every server.
     #[clap(short, long)]
-    pub state: Option<ContainerStateStatusEnum>,
+    pub wide: bool,
+    /// Show the current resource usage of running servers
+    #[clap(long)]
+    pub stats: bool,
+    /// Keep the list on screen and update it whenever a server changes
+    #[clap(long)]
+    pub watch: bool,
+    /// Also list containers labelled as dgs servers that dgs cannot manage
+    #[clap(long)]
+    pub show_invalid: bool,
 }
-pub async fn ls(
-    ServerFilter {
-        name,
-        game,
-        tags,
-        state: status,
-    }: ServerFilter,
-    docker: &Docker,
-) -> Result<()> {
-    let mut filters = HashMap::new();
-    filters.insert(
-        "label".to_owned(),
-        if tags.is_empty() {
-            tags.iter()
-                .map(|tag| "dgs-".to_owned() + tag)
-                // The default Tag every server has
-                .chain(iter::once("dgs".into()))
-                .collect()
-        } else {
-            vec!["dgs".into()]
-        },
-    );
-    if let Some(game_name) = game {
-        let game = GAMES.iter().find(|game| game.name == game_name);
-        let game = game.ok_or_else(|| {
-            let games: Vec<_> = GAMES
-                .iter()
-                .filter(|game| game.name.contains(&*game_name))
-                .collect();
-            match games.len() {
-                1 => Ok(games[0]),
-                0 => bail!("Unable to find a matching game for: `{}`", &*game_name),
 
-                _ => bail!(
-                    "Unable to find unique matching game for: `{}`, found: {}",
-                    &*game_name,
-                    games
-                        .iter()
-                        .map(|game| "`".to_owned() + &game.name + "`")
-                        .intersperse(", ".to_owned())
-                        .collect::<String>()
-                ),
-            }
-        });
-        if let Ok(game) = game {
-            filters.insert("ancestor".into(), vec![game.image.into()]);
+/// Information only shown with `--wide` or `--stats`
+#[derive(Default)]
+struct Details {
+    limits: Option<Limits>,
+    status: Option<Result<ServerStatus>>,
+    usage: Option<Usage>,
+}
+
+impl Details {
+    async fn fetch(host: &Host, server: &BasicServerInfo, options: &ListOptions) -> Self {
+        let running = server.status == ContainerStateStatusEnum::RUNNING;
+        Self {
+            limits: if options.wide {
+                host.manager.limits(&server.id).await.ok()
+            } else {
+                None
+            },
+            status: if options.wide && running {
+                query(server, &host.address).await
+            } else {
+                None
+            },
+            usage: if options.stats && running {
+                host.manager.stats(&server.id).await.ok()
+            } else {
+                None
+            },
         }
-    };
-    if let Some(status) = status {
-        filters.insert("status".into(), vec![status.to_string().to_lowercase()]);
     }
-    let search_name = name.map(|s| s.to_lowercase()).unwrap_or_default();
-    let servers = &docker
-        .list_containers(Some(ListContainersOptions::<String> {
-            all: true,
-            filters,
-            ..Default::default()
+}
+
+async fn list_details(
+    options: &ListOptions,
+    host: &Host,
+) -> Result<(Vec<(BasicServerInfo, Details)>, Vec<InvalidServer>)> {
+    let (servers, invalid) = host.manager.list_checked(&options.filter).await?;
+    let servers = if options.wide || options.stats {
+        join_all(servers.into_iter().map(|server| async move {
+            let details = Details::fetch(host, &server, options).await;
+            (server, details)
         }))
         .await
-        .unwrap();
-    let mut table = Table::new();
-    table
-        .load_preset(UTF8_FULL)
-        .apply_modifier(UTF8_SOLID_INNER_BORDERS)
-        .set_content_arrangement(ContentArrangement::Dynamic)
-        .set_header(
-            vec!["Name", "Game", "Tags", "Ports", "Status"]
-                .iter()
-                .map(|s| Cell::new(s).set_alignment(CellAlignment::Center)),
-        );
+    } else {
+        servers
+            .into_iter()
+            .map(|server| (server, Details::default()))
+            .collect()
+    };
+    Ok((servers, invalid))
+}
 
-    if !table.is_tty() {
-        table.set_table_width(60);
+/// Prints the servers of all `hosts` matching the filter
+///
+/// With `--watch` the table is redrawn on every event of a dgs server instead.
+pub async fn ls(options: ListOptions, hosts: &[Host]) -> Result<()> {
+    if !options.watch {
+        return print_table(&options, hosts).await;
+    }
+    let mut events = select_all(hosts.iter().map(|host| Box::pin(host.manager.events())));
+    loop {
+        print!("{}{}", clear::All, cursor::Goto(1, 1));
+        print_table(&options, hosts).await?;
+        // Unreachable hosts end their stream, which is reported by the next table
+        if events.next().await.is_none() {
+            return Err(
+                Error::EngineUnreachable("Lost the connection to every host".into()).into(),
+            );
+        }
     }
+}
+
+/// Prints a single table of the servers of all `hosts` matching the filter
+///
+/// The hosts are queried concurrently, if there is more than one host, hosts
+/// that cannot be reached are reported as warnings.
+async fn print_table(options: &ListOptions, hosts: &[Host]) -> Result<()> {
+    let results = join_all(hosts.iter().map(|host| async move {
+        let servers = timeout(
+            Duration::from_secs(host.time_out),
+            list_details(options, host),
+        )
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("Timed out after {}s", host.time_out)));
+        (host, servers)
+    }))
+    .await;
 
-    for server in servers {
-        if let Ok(BasicServerInfo {
-            name,
-            game: Game {
-                name: game_name, ..
+    let show_host = hosts.len() > 1;
+    let mut table = table(
+        show_host
+            .then(|| "Host")
+            .into_iter()
+            .chain(vec!["Name", "Game", "Tags", "Ports", "Status", "Health"])
+            .chain(options.show_invalid.then(|| "Problem"))
+            .chain(options.wide.then(|| "Limits"))
+            .chain(options.wide.then(|| "Game Status"))
+            .chain(
+                options
+                    .stats
+                    .then(|| USAGE_HEADER.iter().copied())
+                    .into_iter()
+                    .flatten(),
+            ),
+    );
+
+    let mut warnings = Vec::new();
+    let mut skipped = Vec::new();
+    for (host, servers) in results {
+        let (servers, invalid) = match servers {
+            Ok(servers) => servers,
+            Err(e) if show_host => {
+                warnings.push(format!("Unable to reach host `{}`: {}", host.name, e));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        for (
+            BasicServerInfo {
+                name,
+                game: Game {
+                    name: game_name, ..
+                },
+                tags,
+                ports,
+                status,
+                health,
+                server_type,
+                ..
+            },
+            Details {
+                limits,
+                status: game_status,
+                usage,
             },
-            tags,
-            ports,
-            status,
-        }) = BasicServerInfo::try_from(server.clone())
+        ) in servers
         {
-            if name.to_lowercase().contains(&search_name) {
-                table.add_row(vec![
-                    Cell::new(name),
-                    Cell::new(game_name),
-                    Cell::new(
-                        tags.iter()
-                            .map(|tag| format!(" - {}\n", tag))
-                            .collect::<String>(),
-                    ),
-                    Cell::new(
-                        ports
-                            .iter()
-                            .map(|port| match port {
-                                Port {
-                                    typ: PortTypeEnum::TCP,
-                                    public,
-                                    ..
-                                } => format!(" - {}\n", public),
-                                Port { typ, public, .. } => {
-                                    format!(" - {}({})\n", public, typ)
-                                }
+            table.add_row(
+                show_host
+                    .then(|| Cell::new(&host.name))
+                    .into_iter()
+                    .chain(vec![
+                        Cell::new(name),
+                        Cell::new(match server_type {
+                            Some(server_type) => format!("{} ({})", game_name, server_type),
+                            None => game_name.to_string(),
+                        }),
+                        Cell::new(
+                            tags.iter()
+                                .map(|tag| format!(" - {}\n", tag))
+                                .collect::<String>(),
+                        ),
+                        Cell::new(
+                            ports
+                                .iter()
+                                .map(|port| match port {
+                                    Port {
+                                        typ: PortTypeEnum::TCP,
+                                        public,
+                                        ..
+                                    } => format!(" - {}\n", public),
+                                    Port { typ, public, .. } => {
+                                        format!(" - {}({})\n", public, typ)
+                                    }
+                                })
+                                .collect::<String>(),
+                        ),
+                        Cell::new(format!("{:?}", status)),
+                        Cell::new(
+                            health
+                                .map(|health| health.to_string())
+                                .unwrap_or_else(|| "-".into()),
+                        ),
+                    ])
+                    .chain(options.show_invalid.then(|| Cell::new("-")))
+                    .chain(options.wide.then(|| {
+                        Cell::new(
+                            limits
+                                .map(|limits| limits.to_string())
+                                .unwrap_or_else(|| "?".into()),
+                        )
+                    }))
+                    .chain(options.wide.then(|| {
+                        Cell::new(match game_status {
+                            Some(Ok(game_status)) => game_status.to_string(),
+                            Some(Err(e)) => format!("query failed: {}", e),
+                            None => "-".into(),
+                        })
+                    }))
+                    .chain(
+                        options
+                            .stats
+                            .then(|| match usage {
+                                Some(usage) => usage_cells(&usage),
+                                None => vec![Cell::new("-"); USAGE_HEADER.len()],
                             })
-                            .collect::<String>(),
+                            .into_iter()
+                            .flatten(),
                     ),
-                    Cell::new(format!("{:?}", status)),
-                ]);
+            );
+        }
+        for server in invalid {
+            if !options.show_invalid {
+                skipped.push((host, server));
+                continue;
             }
+            // Limits, game status and usage are unknown
+            let unknown = if options.wide { 2 } else { 0 }
+                + if options.stats { USAGE_HEADER.len() } else { 0 };
+            table.add_row(
+                show_host
+                    .then(|| Cell::new(&host.name))
+                    .into_iter()
+                    .chain(vec![
+                        Cell::new(server.name()),
+                        Cell::new(format!(
+                            "? ({})",
+                            server.image.as_deref().unwrap_or("no image")
+                        )),
+                        Cell::new("-"),
+                        Cell::new("-"),
+                        Cell::new(server.state.as_deref().unwrap_or("?")),
+                        Cell::new("-"),
+                        Cell::new(&server.reason),
+                    ])
+                    .chain(vec![Cell::new("-"); unknown]),
+            );
         }
     }
     println!("{}", table);
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if !skipped.is_empty() {
+        eprintln!(
+            "Warning: Skipped {} container(s) labelled as dgs servers:",
+            skipped.len()
+        );
+        for (host, server) in skipped {
+            if show_host {
+                eprintln!(
+                    " - `{}` on `{}`: {}",
+                    server.name(),
+                    host.name,
+                    server.reason
+                );
+            } else {
+                eprintln!(" - `{}`: {}", server.name(), server.reason);
+            }
+        }
+        eprintln!("Use `--show-invalid` to list them or `dgs doctor` for details");
+    }
     Ok(())
 }
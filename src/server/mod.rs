@@ -1,21 +1,30 @@
+mod adopt;
+mod cmd;
+mod export;
+mod info;
 pub mod ls;
+mod players;
+mod rcon;
+mod saves;
+mod stats;
 mod tmp;
 
-use std::collections::HashMap;
-
-use anyhow::bail;
-use anyhow::{anyhow, Result};
-use bollard::models::PortBinding;
-use bollard::Docker;
-use chrono::prelude::*;
+pub use adopt::{adopt, Adopt};
+use anyhow::Result;
 use clap::Clap;
+pub use cmd::{cmd, Cmd};
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::{Cell, CellAlignment, ContentArrangement, Table};
+pub use export::{export, Export};
+pub use info::{info, Info};
+pub use ls::{ls, ListOptions};
+pub use players::{players, Players};
+pub use rcon::{rcon, Rcon};
+pub use saves::{saves, Saves};
+pub use stats::{stats, Stats};
+pub use tmp::{tmp, Tmp};
 
-use futures_util::TryStreamExt;
-pub use ls::{ls, ServerFilter};
-pub use tmp::{tmp, Tmp, GameOptions};
-use portpicker::pick_unused_port;
-
-use crate::{Game, VersionConfiguration};
+use crate::UTF8_SOLID_INNER_BORDERS;
 
 #[derive(Clap)]
 pub enum ServerCmd {
@@ -23,130 +32,83 @@ pub enum ServerCmd {
     ///
     /// This wont have persistant storage and stop when exited (e.g. with <^C>)
     Tmp(Tmp),
-    Ls(ServerFilter),
+    Ls(ListOptions),
+    /// Show the resource usage of running servers
+    Stats(Stats),
+    /// Show everything dgs knows about a server
+    Info(Info),
+    /// Send commands to a server via RCON
+    Rcon(Rcon),
+    /// Send a single line to the console of a server
+    ///
+    /// Works for every game, even those without RCON.
+    Cmd(Cmd),
+    /// Manage the whitelist, operators and bans of a server
+    Players(Players),
+    /// List the saves or worlds of a server
+    Saves(Saves),
+    /// Let dgs manage an existing container of a supported game
+    ///
+    /// The container is recreated with the same configuration and volumes,
+    /// as labels cannot be added to existing containers.
+    Adopt(Adopt),
+    /// Describe a server for running it without dgs e.g. with docker-compose
+    Export(Export),
 }
 
-async fn create(docker: &Docker, game: &'static Game, options: GameOptions) -> Result<String> {
-    use bollard::container::{Config, CreateContainerOptions};
-    use bollard::models::HostConfig;
-    let mut pb: HashMap<String, Option<Vec<PortBinding>>> = HashMap::new();
-    match game.ports {
-        crate::PortConfiguration::NonConfigurable(_) => todo!(),
-        crate::PortConfiguration::SinglePort(port, protocol) => {
-            let host_port =
-                pick_unused_port().ok_or_else(|| anyhow!("Did not find any open port LUL."))?;
-            println!("Running on Port: `{}`", host_port);
-            pb.insert(
-                format!("{}/{}", port, protocol),
-                Some(vec![PortBinding {
-                    host_ip: None,
-                    host_port: Some(host_port.to_string()),
-                }]),
-            );
+impl ServerCmd {
+    /// Whether the command should run against every configured host
+    pub fn all_hosts(&self) -> bool {
+        match self {
+            ServerCmd::Tmp(_)
+            | ServerCmd::Info(_)
+            | ServerCmd::Rcon(_)
+            | ServerCmd::Cmd(_)
+            | ServerCmd::Players(_)
+            | ServerCmd::Saves(_)
+            | ServerCmd::Adopt(_)
+            | ServerCmd::Export(_) => false,
+            ServerCmd::Ls(options) | ServerCmd::Stats(Stats { options }) => options.all_hosts,
         }
     }
-    let mut envs: Vec<_> = game.envs.into();
-    let v = if let (VersionConfiguration::Env(name), Some(version)) =
-        (game.version.config.clone(), options.version)
-    {
-        Some(format!("{}={}", name, version))
-    } else {
-        None
-    };
-    let v = v.as_deref();
 
-    if v.is_some() {
-        envs.push(v.unwrap());
+    /// Checks arguments clap cannot validate on its own e.g. rules spanning multiple options
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            ServerCmd::Tmp(tmp) => tmp.validate(),
+            ServerCmd::Export(export) => export.validate(),
+            _ => Ok(()),
+        }
     }
-    let config = Config {
-        image: Some(game.image),
-        env: Some(envs),
-        host_config: Some(HostConfig {
-            port_bindings: Some(pb),
-            ..Default::default()
-        }),
-        labels: {
-            let mut labels = HashMap::new();
-            labels.insert("dgs", "dgs");
-            Some(labels)
-        },
-
-        ..Default::default()
-    };
-
-    Ok(docker
-        .create_container(
-            Some(CreateContainerOptions {
-                name: format!(
-                    "dgs-tmp_{}_{}",
-                    game.name,
-                    Local::now().format("%Y-%m-%d_%H-%M-%S%.3f")
-                ),
-            }),
-            config,
-        )
-        .await?
-        .id)
 }
 
-async fn start(docker: &Docker, container_id: &str) -> Result<()> {
-    use bollard::container::StartContainerOptions;
-    let options = Some(StartContainerOptions { detach_keys: "" });
+/// A table in the style used for all dgs output
+fn table<T: ToString>(header: impl IntoIterator<Item = T>) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_SOLID_INNER_BORDERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(
+            header
+                .into_iter()
+                .map(|s| Cell::new(s).set_alignment(CellAlignment::Center)),
+        );
 
-    Ok(docker.start_container(container_id, options).await?)
-}
-
-async fn stop(docker: &Docker, container_id: &str) -> Result<()> {
-    Ok(docker.stop_container(container_id, None).await?)
-}
-async fn rm(docker: &Docker, container_id: &str) -> Result<()> {
-    Ok(docker.remove_container(container_id, None).await?)
+    if !table.is_tty() {
+        table.set_table_width(60);
+    }
+    table
 }
 
-fn pause() {
+fn pause() -> Result<()> {
     use std::io::{stdin, stdout, Write};
     use termion::input::TermRead;
     use termion::raw::IntoRawMode;
 
     println!("Press any key to quit the server...");
-    let mut stdout = stdout().into_raw_mode().unwrap();
-    stdout.flush().unwrap();
+    let mut stdout = stdout().into_raw_mode()?;
+    stdout.flush()?;
     stdin().events().next();
-}
-
-async fn pull(docker: &Docker, image_name: &str, tag: Option<&str>) -> Result<()> {
-    use bollard::image::CreateImageOptions;
-    use bollard::models::ProgressDetail;
-    println!("Pulling {}", image_name);
-    let mut options = CreateImageOptions {
-        from_image: image_name,
-        repo: "docker.io",
-        ..Default::default()
-    };
-    if let Some(tag) = tag {
-        options.tag = tag
-    }
-    docker
-        .create_image(Some(options), None, None)
-        .err_into::<anyhow::Error>()
-        .try_for_each(|progress| async move {
-            if let Some(error) = progress.error {
-                bail!(error);
-            }
-            let status = progress
-                .status
-                .unwrap_or_else(|| "Downloading image".into());
-            if let Some(ProgressDetail {
-                current: Some(current),
-                total: Some(total),
-            }) = progress.progress_detail
-            {
-                println!("{}: ({}/{})", status, current, total);
-            } else {
-                println!("{}", status);
-            }
-            Ok(())
-        })
-        .await?;
     Ok(())
 }
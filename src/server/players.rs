@@ -0,0 +1,172 @@
+use core::fmt;
+use std::fmt::Display;
+
+use anyhow::Result;
+use clap::Clap;
+use docker_game_servers::archive::Archive;
+use docker_game_servers::{BasicServerInfo, Error, PlayerManagement};
+
+use super::cmd::{print_output, send};
+use crate::host::Host;
+
+#[derive(Clap)]
+pub struct Players {
+    /// Name of the server
+    name: String,
+    #[clap(subcommand)]
+    cmd: PlayersCmd,
+}
+
+#[derive(Clap)]
+pub enum PlayersCmd {
+    /// Players allowed to join
+    Whitelist(ListCmd),
+    /// Players with operator or admin rights
+    Op(ListCmd),
+    /// Players not allowed to join
+    Ban(ListCmd),
+    /// Disconnect a player
+    Kick { player: String },
+}
+
+#[derive(Clap)]
+pub enum ListCmd {
+    Add { player: String },
+    Rm { player: String },
+    Ls,
+}
+
+#[derive(Clone, Copy)]
+enum PlayerList {
+    Whitelist,
+    Ops,
+    Bans,
+}
+
+impl PlayerList {
+    fn pick<T>(self, whitelist: T, ops: T, bans: T) -> T {
+        match self {
+            PlayerList::Whitelist => whitelist,
+            PlayerList::Ops => ops,
+            PlayerList::Bans => bans,
+        }
+    }
+}
+
+impl Display for PlayerList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.pick("whitelist", "operators", "bans"))
+    }
+}
+
+/// Reads a player list file, a missing file is an empty list
+async fn read_list(host: &Host, server: &BasicServerInfo, path: &str) -> Result<Vec<String>> {
+    let content = host
+        .manager
+        .exec(
+            &server.id,
+            vec![
+                "sh".into(),
+                "-c".into(),
+                r#"cat "$0" 2>/dev/null || true"#.into(),
+                path.into(),
+            ],
+        )
+        .await?;
+    Ok(content
+        .lines()
+        .map(|line| line.trim().to_string())
+        .collect())
+}
+
+async fn write_list(
+    host: &Host,
+    server: &BasicServerInfo,
+    path: &str,
+    lines: &[String],
+) -> Result<()> {
+    let content = lines.join("\n") + "\n";
+    host.manager
+        .upload(
+            &server.id,
+            Archive::default().file(path, content.as_bytes())?,
+        )
+        .await
+}
+
+pub async fn players(Players { name, cmd }: Players, host: &Host) -> Result<()> {
+    let server = host.manager.find(&name).await?;
+    let management = server
+        .game
+        .players
+        .as_ref()
+        .ok_or_else(|| Error::unsupported(server.game.name, "managing players"))?;
+    let (list, cmd) = match cmd {
+        PlayersCmd::Whitelist(cmd) => (PlayerList::Whitelist, cmd),
+        PlayersCmd::Op(cmd) => (PlayerList::Ops, cmd),
+        PlayersCmd::Ban(cmd) => (PlayerList::Bans, cmd),
+        PlayersCmd::Kick { player } => {
+            return match management {
+                PlayerManagement::Commands { kick, .. } => {
+                    print_output(&send(host, &server, kick.replace("{player}", &player)).await?);
+                    Ok(())
+                }
+                PlayerManagement::Files { .. } => {
+                    Err(Error::unsupported(server.game.name, "kicking players").into())
+                }
+            };
+        }
+    };
+
+    match management {
+        PlayerManagement::Commands {
+            whitelist,
+            ops,
+            bans,
+            ..
+        } => {
+            let commands = list.pick(whitelist, ops, bans);
+            let command = match cmd {
+                ListCmd::Add { player } => commands.add.replace("{player}", &player),
+                ListCmd::Rm { player } => commands.remove.replace("{player}", &player),
+                ListCmd::Ls => commands
+                    .list
+                    .ok_or_else(|| {
+                        Error::unsupported(server.game.name, format!("listing its {}", list))
+                    })?
+                    .into(),
+            };
+            print_output(&send(host, &server, command).await?);
+        }
+        PlayerManagement::Files {
+            whitelist,
+            ops,
+            bans,
+        } => {
+            let path = list.pick(whitelist, ops, bans);
+            let mut players = read_list(host, &server, path).await?;
+            match cmd {
+                ListCmd::Add { player } => {
+                    if !players.contains(&player) {
+                        players.push(player);
+                        write_list(host, &server, path, &players).await?;
+                    }
+                }
+                ListCmd::Rm { player } => {
+                    players.retain(|line| *line != player);
+                    write_list(host, &server, path, &players).await?;
+                }
+                ListCmd::Ls => {
+                    // Lines starting with `//` are comments
+                    for player in players
+                        .iter()
+                        .filter(|line| !line.is_empty() && !line.starts_with("//"))
+                    {
+                        println!("{}", player);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use std::io::{stdout, Write};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use bollard::models::PortTypeEnum;
+use clap::Clap;
+use docker_game_servers::rcon::Client;
+use docker_game_servers::{BasicServerInfo, Error};
+use tokio::io::{stdin, AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::host::Host;
+
+#[derive(Clap)]
+pub struct Rcon {
+    /// Name of the server
+    name: String,
+    /// Command to run, without a command an interactive session is started
+    command: Vec<String>,
+}
+
+pub(super) async fn connect(host: &Host, server: &BasicServerInfo) -> Result<Client<TcpStream>> {
+    // The port is only published on the loopback interface of the host
+    if host.address != "localhost" {
+        bail!(
+            "RCON is only reachable from the host `{}` itself",
+            host.name
+        );
+    }
+    let rcon = server
+        .game
+        .rcon
+        .as_ref()
+        .ok_or_else(|| Error::unsupported(server.game.name, "RCON"))?;
+    let password = host
+        .manager
+        .rcon_password(&server.id)
+        .await?
+        .ok_or_else(|| anyhow!("`{}` was created without RCON", server.name))?;
+    let port = server
+        .public_port(rcon.port, PortTypeEnum::TCP)
+        .ok_or_else(|| anyhow!("The RCON port of `{}` is not published", server.name))?;
+    timeout(
+        Duration::from_secs(host.time_out),
+        Client::connect("127.0.0.1", port, &password),
+    )
+    .await
+    .unwrap_or_else(|_| Err(anyhow!("No answer after {}s", host.time_out)))
+}
+
+pub async fn rcon(Rcon { name, command }: Rcon, host: &Host) -> Result<()> {
+    let server = host.manager.find(&name).await?;
+    let mut client = connect(host, &server).await?;
+
+    if !command.is_empty() {
+        println!("{}", client.command(&command.join(" ")).await?);
+        return Ok(());
+    }
+
+    println!("Connected to `{}`, exit with <^D>", server.name);
+    let mut lines = BufReader::new(stdin()).lines();
+    loop {
+        print!("> ");
+        stdout().flush()?;
+        let line = match lines.next_line().await? {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        let line = line.trim();
+        if !line.is_empty() {
+            println!("{}", client.command(line).await?);
+        }
+    }
+}
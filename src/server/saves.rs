@@ -0,0 +1,61 @@
+use anyhow::{bail, Result};
+use bollard::models::ContainerStateStatusEnum;
+use clap::Clap;
+use docker_game_servers::Error;
+
+use super::stats::human_bytes;
+use super::table;
+use crate::host::Host;
+
+#[derive(Clap)]
+pub struct Saves {
+    /// Name of the server
+    name: String,
+}
+
+/// Lists the saves or worlds stored in the volume of a server
+///
+/// The files are read from inside the container, so it has to be running.
+pub async fn saves(Saves { name }: Saves, host: &Host) -> Result<()> {
+    let server = host.manager.find(&name).await?;
+    let dir = server
+        .game
+        .saves
+        .ok_or_else(|| Error::unsupported(server.game.name, "listing saves"))?;
+    if server.status != ContainerStateStatusEnum::RUNNING {
+        bail!("`{}` has to be running to list its saves", name);
+    }
+    // A missing directory means the server has not saved yet
+    let output = host
+        .manager
+        .exec(
+            &server.id,
+            vec![
+                "sh".into(),
+                "-c".into(),
+                r#"cd "$0" 2>/dev/null && stat --printf '%n\t%s\t%y\n' -- * 2>/dev/null || true"#
+                    .into(),
+                dir.into(),
+            ],
+        )
+        .await?;
+
+    let mut table = table(vec!["Name", "Size", "Modified"]);
+    for line in output.lines() {
+        let mut fields = line.splitn(3, '\t');
+        if let (Some(name), Some(size), Some(modified)) =
+            (fields.next(), fields.next(), fields.next())
+        {
+            table.add_row(vec![
+                name.to_string(),
+                size.parse()
+                    .map(human_bytes)
+                    .unwrap_or_else(|_| size.into()),
+                // Cut the seconds and time zone from e.g. `2021-08-20 18:03:12.000000000 +0000`
+                modified.get(..16).unwrap_or(modified).to_string(),
+            ]);
+        }
+    }
+    println!("{}", table);
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use bollard::models::ContainerStateStatusEnum;
+use clap::Clap;
+use comfy_table::Cell;
+use docker_game_servers::engine::Engine;
+use docker_game_servers::resources::Usage;
+use docker_game_servers::{BasicServerInfo, ServerFilter, ServerManager};
+use futures_util::future::join_all;
+use termion::{clear, cursor};
+use tokio::time::{sleep, timeout};
+
+use super::ls::ListOptions;
+use super::table;
+use crate::host::Host;
+
+/// Seconds between two refreshes with `--watch`
+///
+/// There is no event for changed usage so this has to poll.
+const REFRESH: u64 = 2;
+
+#[derive(Clap)]
+pub struct Stats {
+    #[clap(flatten)]
+    pub options: ListOptions,
+}
+
+pub const USAGE_HEADER: [&str; 4] = ["CPU %", "Memory", "Net RX/TX", "Block IO"];
+
+/// The cells of `usage` in the order of [`USAGE_HEADER`]
+pub fn usage_cells(usage: &Usage) -> Vec<Cell> {
+    vec![
+        Cell::new(format!("{:.1}%", usage.cpu)),
+        Cell::new(format!(
+            "{} / {}",
+            human_bytes(usage.memory),
+            human_bytes(usage.memory_limit)
+        )),
+        Cell::new(format!(
+            "{} / {}",
+            human_bytes(usage.rx),
+            human_bytes(usage.tx)
+        )),
+        Cell::new(format!(
+            "{} / {}",
+            human_bytes(usage.block_read),
+            human_bytes(usage.block_write)
+        )),
+    ]
+}
+
+/// Formats `bytes` with binary prefixes e.g. `1.5GiB`
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+/// The usage is `None` for servers that stopped or were removed in the meantime
+async fn list_usage<E: Engine>(
+    filter: &ServerFilter,
+    manager: &ServerManager<E>,
+) -> Result<Vec<(BasicServerInfo, Option<Usage>)>> {
+    let servers = manager.list(filter).await?;
+    Ok(join_all(servers.into_iter().map(|server| async move {
+        let usage = manager.stats(&server.id).await.ok();
+        (server, usage)
+    }))
+    .await)
+}
+
+/// Prints the resource usage of all running servers matching the filter
+pub async fn stats(Stats { mut options }: Stats, hosts: &[Host]) -> Result<()> {
+    options
+        .filter
+        .state
+        .get_or_insert(ContainerStateStatusEnum::RUNNING);
+    let filter = &options.filter;
+    let show_host = hosts.len() > 1;
+    loop {
+        let results = join_all(hosts.iter().map(|host| async move {
+            let servers = timeout(
+                Duration::from_secs(host.time_out),
+                list_usage(filter, &host.manager),
+            )
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("Timed out after {}s", host.time_out)));
+            (host, servers)
+        }))
+        .await;
+
+        let mut table = table(
+            show_host
+                .then(|| "Host")
+                .into_iter()
+                .chain(Some("Name"))
+                .chain(USAGE_HEADER.iter().copied()),
+        );
+        let mut warnings = Vec::new();
+        for (host, servers) in results {
+            let servers = match servers {
+                Ok(servers) => servers,
+                Err(e) if show_host => {
+                    warnings.push(format!("Unable to reach host `{}`: {}", host.name, e));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            for (BasicServerInfo { name, .. }, usage) in servers {
+                table.add_row(
+                    show_host
+                        .then(|| Cell::new(&host.name))
+                        .into_iter()
+                        .chain(Some(Cell::new(name)))
+                        .chain(match usage {
+                            Some(usage) => usage_cells(&usage),
+                            None => vec![Cell::new("-"); USAGE_HEADER.len()],
+                        }),
+                );
+            }
+        }
+
+        if options.watch {
+            print!("{}{}", clear::All, cursor::Goto(1, 1));
+        }
+        println!("{}", table);
+        for warning in warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        if !options.watch {
+            return Ok(());
+        }
+        sleep(Duration::from_secs(REFRESH)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bollard::container::Config;
+    use docker_game_servers::engine::fake::{FakeContainer, FakeEngine};
+
+    use super::*;
+
+    fn server(name: &str) -> FakeContainer {
+        let mut labels = std::collections::HashMap::new();
+        labels.insert("dgs".to_string(), "dgs".to_string());
+        FakeContainer::new(
+            name,
+            Config {
+                image: Some("itzg/minecraft-server".into()),
+                labels: Some(labels),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn stopped_servers_have_no_usage() {
+        let engine = FakeEngine::default();
+        let mut survival = server("survival");
+        survival.usage = Usage {
+            cpu: 12.5,
+            memory: 1024,
+            ..Default::default()
+        };
+        let id = engine.insert(survival);
+        engine.start(&id).await.unwrap();
+        engine.insert(server("creative"));
+        let manager = ServerManager::new(engine);
+
+        let mut usage = list_usage(&ServerFilter::default(), &manager)
+            .await
+            .unwrap();
+        usage.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        assert_eq!(usage[0].0.name, "/creative");
+        assert_eq!(usage[0].1, None);
+        assert_eq!(usage[1].1.map(|usage| usage.cpu), Some(12.5));
+        assert_eq!(usage_cells(&usage[1].1.unwrap()).len(), USAGE_HEADER.len());
+    }
+}
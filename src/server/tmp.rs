@@ -1,35 +1,73 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Clap;
+use docker_game_servers::{Game, GameOptions, ServerManager};
+use futures_util::TryStreamExt;
+
+use super::pause;
+use crate::license::ensure_accepted;
+
 #[derive(Clap)]
 pub struct Tmp {
     game: &'static Game,
     #[clap(flatten)]
     options: GameOptions,
+    /// Wait until the health check reports the server as ready
+    #[clap(long)]
+    wait: bool,
+    /// Seconds to wait for the server with `--wait`, by default as long as
+    /// the health check of the game may take
+    #[clap(long, requires = "wait")]
+    wait_timeout: Option<u64>,
 }
 
-#[derive(Clap)]
-pub struct GameOptions {
-    #[clap(long, short)]
-    version: Option<String>,
+impl Tmp {
+    pub fn validate(&self) -> Result<()> {
+        self.options.validate(self.game)
+    }
 }
 
-pub async fn tmp(docker: &Docker, Tmp { game, options }: Tmp) -> Result<()> {
-    pull(
-        docker,
-        game.image,
-        if game.version.config == VersionConfiguration::Tag {
-            options.version.as_deref()
-        } else {
-            None
-        },
-    )
-    .await?;
-    let container_id = create(docker, game, options).await?;
-    start(docker, &container_id).await?;
-
-    pause();
+pub async fn tmp(
+    manager: &ServerManager,
+    Tmp {
+        game,
+        mut options,
+        wait,
+        wait_timeout,
+    }: Tmp,
+) -> Result<()> {
+    ensure_accepted(game, options.accept_eula)?;
+    options.accept_eula = true;
+    println!("Pulling {}", game.image);
+    manager
+        .pull(game, options.version.as_deref())
+        .try_for_each(|progress| async move {
+            println!("{}", progress);
+            Ok(())
+        })
+        .await?;
+    let server = manager.create(game, options).await?;
+    for port in &server.ports {
+        println!("Running on Port: `{}/{}`", port.public, port.typ);
+    }
+    let container_id = &server.id;
+    if wait {
+        println!("Waiting for the server to get ready...");
+        manager
+            .start_healthy(container_id, wait_timeout.map(Duration::from_secs))
+            .await?;
+        println!("The server is ready");
+    } else if let Err(e) = manager.start(container_id).await {
+        manager.remove(container_id).await.ok();
+        return Err(e);
+    }
+
+    let paused = pause();
 
     // TODO option to attach to console
-    stop(docker, &container_id).await?;
-    rm(docker, &container_id).await?;
+    manager.stop(container_id).await?;
+    manager.remove(container_id).await?;
 
-    Ok(())
-}
\ No newline at end of file
+    paused
+}
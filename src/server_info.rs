@@ -0,0 +1,272 @@
+//! Servers as they are listed by the engine
+use core::fmt;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Error, Result};
+use bollard::models::{
+    self, ContainerStateStatusEnum, ContainerSummaryInner, HealthStatusEnum, PortTypeEnum,
+};
+
+use crate::game::{Game, SERVER_TYPE_LABEL};
+
+#[derive(Debug)]
+pub struct Port {
+    pub public: u16,
+    pub private: u16,
+    pub typ: PortTypeEnum,
+}
+
+impl TryFrom<models::Port> for Port {
+    type Error = Error;
+
+    fn try_from(value: models::Port) -> Result<Self, Self::Error> {
+        match value {
+            models::Port {
+                private_port,
+                public_port: Some(public_port),
+                typ: Some(typ),
+                ..
+            } => Ok(Self {
+                public: public_port.try_into()?,
+                private: private_port.try_into()?,
+                typ,
+            }),
+            port => Err(anyhow!("Incompatible port config: {:?}", port)),
+        }
+    }
+}
+
+pub struct BasicServerInfo {
+    pub id: String,
+    pub name: String,
+    pub game: &'static Game,
+    pub tags: Vec<String>,
+    pub ports: Vec<Port>,
+    pub status: ContainerStateStatusEnum,
+    /// Only set for servers with a health check
+    pub health: Option<HealthStatusEnum>,
+    /// Only set for games with different server software e.g. `paper`
+    pub server_type: Option<String>,
+}
+
+impl BasicServerInfo {
+    /// The port on the host that `private` is published on
+    pub fn public_port(&self, private: u16, typ: PortTypeEnum) -> Option<u16> {
+        self.ports
+            .iter()
+            .find(|port| port.private == private && port.typ == typ)
+            .map(|port| port.public)
+    }
+}
+
+impl fmt::Debug for BasicServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            id,
+            name,
+            game: Game { name: game, .. },
+            tags,
+            ports,
+            status,
+            health,
+            server_type,
+        } = self;
+        write!(
+            f,
+            "Server {{id: {:?}, name: {:?}, game: {:?}, tags: {:?}, ports: {:?}, status: {:?}, health: {:?}, server_type: {:?}}}",
+            id,
+            name,
+            game,
+            tags,
+            ports
+                .iter()
+                .map(
+                    |Port {
+                         public,
+                         typ,
+                         private,
+                         ..
+                     }| format!("{}:{}->{}", typ, public, private)
+                )
+                .collect::<Vec<_>>(),
+            status,
+            health,
+            server_type
+        )
+    }
+}
+
+impl TryFrom<ContainerSummaryInner> for BasicServerInfo {
+    type Error = Error;
+
+    fn try_from(container: ContainerSummaryInner) -> Result<Self, Self::Error> {
+        let ContainerSummaryInner {
+            id,
+            image,
+            names,
+            labels,
+            ports,
+            state,
+            status,
+            ..
+        } = container;
+        let id = id.ok_or_else(|| anyhow!("The engine did not return an id"))?;
+        let mut names = names.unwrap_or_default();
+        if names.len() != 1 {
+            bail!("Expected exactly one name, found {}", names.len());
+        }
+        let image = image.ok_or_else(|| anyhow!("The engine did not return an image"))?;
+        let game = Game::find_by_image(&image)
+            .ok_or_else(|| anyhow!("Container image is not compatible with dgs: `{}`", image))?;
+        let mut labels = labels.ok_or_else(|| anyhow!("The engine did not return any labels"))?;
+        let ports = ports.ok_or_else(|| anyhow!("The engine did not return any ports"))?;
+        let state = state.ok_or_else(|| anyhow!("The engine did not return a state"))?;
+        Ok(Self {
+            status: ContainerStateStatusEnum::from_str(&state)
+                .map_err(|_| anyhow!("Invalid container state: `{}`", state))?,
+            id,
+            name: names.remove(0),
+            game,
+            server_type: labels.remove(SERVER_TYPE_LABEL),
+            tags: labels
+                .into_keys()
+                .filter_map(|label| label.strip_prefix("dgs-").map(|label| label.into()))
+                .collect(),
+            ports: ports
+                .into_iter()
+                .filter_map(|port| Port::try_from(port).ok())
+                .collect(),
+            health: status.as_deref().and_then(health_from_status),
+        })
+    }
+}
+
+/// A container labelled as a dgs server that dgs cannot manage
+#[derive(Debug)]
+pub struct InvalidServer {
+    pub id: String,
+    pub names: Vec<String>,
+    pub image: Option<String>,
+    pub state: Option<String>,
+    /// Why [`BasicServerInfo::try_from`] failed
+    pub reason: String,
+}
+
+impl InvalidServer {
+    pub fn new(container: &ContainerSummaryInner, reason: &Error) -> Self {
+        Self {
+            id: container.id.clone().unwrap_or_default(),
+            names: container.names.clone().unwrap_or_default(),
+            image: container.image.clone(),
+            state: container.state.clone(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// The name shown to users, falls back to the short id
+    pub fn name(&self) -> String {
+        match self.names.first() {
+            Some(name) => name.clone(),
+            None => self.id.chars().take(12).collect(),
+        }
+    }
+}
+
+/// Parses the health from the human readable status e.g. `Up 2 hours (healthy)`
+fn health_from_status(status: &str) -> Option<HealthStatusEnum> {
+    if status.ends_with("(healthy)") {
+        Some(HealthStatusEnum::HEALTHY)
+    } else if status.ends_with("(unhealthy)") {
+        Some(HealthStatusEnum::UNHEALTHY)
+    } else if status.ends_with("(health: starting)") {
+        Some(HealthStatusEnum::STARTING)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn container(image: &str) -> ContainerSummaryInner {
+        let mut labels = HashMap::new();
+        labels.insert("dgs".to_string(), "dgs".to_string());
+        labels.insert("dgs-survival".to_string(), "".to_string());
+        labels.insert(SERVER_TYPE_LABEL.to_string(), "paper".to_string());
+        ContainerSummaryInner {
+            id: Some("abc".into()),
+            names: Some(vec!["/survival".into()]),
+            image: Some(image.into()),
+            labels: Some(labels),
+            ports: Some(vec![
+                models::Port {
+                    ip: None,
+                    private_port: 25565,
+                    public_port: Some(25570),
+                    typ: Some(PortTypeEnum::TCP),
+                },
+                // Exposed but not published
+                models::Port {
+                    ip: None,
+                    private_port: 25575,
+                    public_port: None,
+                    typ: Some(PortTypeEnum::TCP),
+                },
+            ]),
+            state: Some("running".into()),
+            status: Some("Up 2 hours (healthy)".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn converts_dgs_containers() {
+        let server = BasicServerInfo::try_from(container("itzg/minecraft-server:latest")).unwrap();
+        assert_eq!(server.id, "abc");
+        assert_eq!(server.name, "/survival");
+        assert_eq!(&*server.game.name, "minecraft");
+        assert_eq!(server.tags, vec!["survival".to_string()]);
+        assert_eq!(server.server_type.as_deref(), Some("paper"));
+        assert_eq!(server.status, ContainerStateStatusEnum::RUNNING);
+        assert_eq!(server.health, Some(HealthStatusEnum::HEALTHY));
+        assert_eq!(server.ports.len(), 1);
+        assert_eq!(server.public_port(25565, PortTypeEnum::TCP), Some(25570));
+    }
+
+    #[test]
+    fn rejects_unknown_images() {
+        assert!(BasicServerInfo::try_from(container("nginx")).is_err());
+    }
+
+    #[test]
+    fn rejects_incomplete_containers() {
+        let mut missing_state = container("itzg/minecraft-server");
+        missing_state.state = None;
+        assert!(BasicServerInfo::try_from(missing_state).is_err());
+
+        let mut multiple_names = container("itzg/minecraft-server");
+        multiple_names.names = Some(vec!["/a".into(), "/b".into()]);
+        assert!(BasicServerInfo::try_from(multiple_names).is_err());
+
+        let mut invalid_state = container("itzg/minecraft-server");
+        invalid_state.state = Some("sleeping".into());
+        assert!(BasicServerInfo::try_from(invalid_state).is_err());
+    }
+
+    #[test]
+    fn parses_health_from_status() {
+        assert_eq!(
+            health_from_status("Up 5 seconds (health: starting)"),
+            Some(HealthStatusEnum::STARTING)
+        );
+        assert_eq!(
+            health_from_status("Up 2 hours (unhealthy)"),
+            Some(HealthStatusEnum::UNHEALTHY)
+        );
+        assert_eq!(health_from_status("Exited (0) 2 hours ago"), None);
+    }
+}